@@ -0,0 +1,423 @@
+//! `#[derive(DgraphNode)]`, re-exported by `dgraph-rs` with its `derive` feature. See the
+//! `node` module of `dgraph-rs` for the attributes it takes.
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, Lit, Meta, NestedMeta, Type};
+
+#[proc_macro_derive(DgraphNode, attributes(dgraph))]
+pub fn derive_dgraph_node(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand(input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Wrapper {
+    None,
+    Option,
+    Vec,
+}
+
+struct Field {
+    ident: syn::Ident,
+    ty: Type,
+    predicate: String,
+    wrapper: Wrapper,
+    inner: Type,
+    uid: bool,
+    edge: bool,
+    skip: bool,
+    scalar_type: Option<String>,
+    indexes: Vec<String>,
+    upsert: bool,
+    reverse: bool,
+    count: bool,
+    lang: bool,
+}
+
+fn error<T>(tokens: impl quote::ToTokens, message: &str) -> syn::Result<T> {
+    Err(syn::Error::new_spanned(tokens, message))
+}
+
+fn dgraph_attrs(attrs: &[syn::Attribute]) -> syn::Result<Vec<NestedMeta>> {
+    let mut nested = Vec::new();
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident("dgraph")) {
+        match attr.parse_meta()? {
+            Meta::List(list) => nested.extend(list.nested),
+            meta => return error(meta, "expected #[dgraph(...)]"),
+        }
+    }
+    Ok(nested)
+}
+
+fn lit_str(lit: &Lit) -> syn::Result<String> {
+    match lit {
+        Lit::Str(s) => Ok(s.value()),
+        lit => error(lit, "expected a string"),
+    }
+}
+
+/// Splits `Option<T>` and `Vec<T>` into the wrapper and `T`
+fn unwrap_type(ty: &Type) -> (Wrapper, Type) {
+    if let Type::Path(path) = ty {
+        if let Some(segment) = path.path.segments.last() {
+            if let syn::PathArguments::AngleBracketed(args) = &segment.arguments {
+                if let (1, Some(syn::GenericArgument::Type(inner))) = (args.args.len(), args.args.first()) {
+                    if segment.ident == "Option" {
+                        return (Wrapper::Option, inner.clone());
+                    }
+                    if segment.ident == "Vec" {
+                        return (Wrapper::Vec, inner.clone());
+                    }
+                }
+            }
+        }
+    }
+    (Wrapper::None, ty.clone())
+}
+
+fn type_name(ty: &Type) -> Option<String> {
+    match ty {
+        Type::Path(path) => path.path.segments.last().map(|s| s.ident.to_string()),
+        Type::Reference(reference) => type_name(&reference.elem),
+        _ => None,
+    }
+}
+
+fn infer_scalar_type(ty: &Type) -> Option<&'static str> {
+    let scalar = match type_name(ty)?.as_str() {
+        "String" | "str" => "string",
+        "i8" | "i16" | "i32" | "i64" | "isize" | "u8" | "u16" | "u32" => "int",
+        "f32" | "f64" => "float",
+        "bool" => "bool",
+        "SystemTime" | "DateTime" | "NaiveDateTime" | "NaiveDate" => "datetime",
+        "Point" | "Polygon" | "MultiPolygon" => "geo",
+        _ => return None,
+    };
+    Some(scalar)
+}
+
+fn is_system_time(ty: &Type) -> bool {
+    type_name(ty).is_some_and(|name| name == "SystemTime")
+}
+
+impl Field {
+    fn parse(field: &syn::Field) -> syn::Result<Self> {
+        let ident = field.ident.clone().expect("named field");
+        let (wrapper, inner) = unwrap_type(&field.ty);
+
+        let mut parsed = Field {
+            predicate: ident.to_string(),
+            uid: ident == "uid",
+            ident,
+            ty: field.ty.clone(),
+            wrapper,
+            inner,
+            edge: false,
+            skip: false,
+            scalar_type: None,
+            indexes: Vec::new(),
+            upsert: false,
+            reverse: false,
+            count: false,
+            lang: false,
+        };
+
+        for meta in dgraph_attrs(&field.attrs)? {
+            match &meta {
+                NestedMeta::Meta(Meta::Path(path)) => {
+                    let flag = match path.get_ident().map(|i| i.to_string()).as_deref() {
+                        Some("uid") => &mut parsed.uid,
+                        Some("edge") => &mut parsed.edge,
+                        Some("skip") => &mut parsed.skip,
+                        Some("upsert") => &mut parsed.upsert,
+                        Some("reverse") => &mut parsed.reverse,
+                        Some("count") => &mut parsed.count,
+                        Some("lang") => &mut parsed.lang,
+                        _ => return error(path, "unknown dgraph attribute"),
+                    };
+                    *flag = true;
+                }
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("predicate") => {
+                    parsed.predicate = lit_str(&nv.lit)?;
+                }
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("type") => {
+                    parsed.scalar_type = Some(lit_str(&nv.lit)?);
+                }
+                NestedMeta::Meta(Meta::List(list)) if list.path.is_ident("index") => {
+                    for tokenizer in &list.nested {
+                        match tokenizer {
+                            NestedMeta::Meta(Meta::Path(path)) if path.get_ident().is_some() => {
+                                parsed.indexes.push(path.get_ident().unwrap().to_string());
+                            }
+                            tokenizer => return error(tokenizer, "expected a tokenizer name"),
+                        }
+                    }
+                }
+                meta => return error(meta, "unknown dgraph attribute"),
+            }
+        }
+
+        if parsed.uid && parsed.edge {
+            return error(&field.ty, "a uid field can't be an edge");
+        }
+
+        if !parsed.uid && !parsed.edge && !parsed.skip && parsed.scalar_type.is_none() {
+            // Dgraph's int is signed 64 bit, so larger unsigned values don't round trip
+            if let Some("u64" | "usize") = type_name(&parsed.inner).as_deref() {
+                return error(
+                    &field.ty,
+                    "u64 and usize may not fit in Dgraph's int, which is a signed 64 bit integer, \
+                     add #[dgraph(type = \"int\")] if the values always do",
+                );
+            }
+            match infer_scalar_type(&parsed.inner) {
+                Some(scalar) => parsed.scalar_type = Some(scalar.to_string()),
+                None => return error(
+                    &field.ty,
+                    "can't infer the Dgraph type, add #[dgraph(type = \"...\")] or #[dgraph(edge)]",
+                ),
+            }
+        }
+
+        Ok(parsed)
+    }
+
+    fn schema(&self) -> String {
+        let ty = match (&self.scalar_type, self.edge) {
+            (_, true) => "uid".to_string(),
+            (Some(scalar), false) => scalar.clone(),
+            (None, false) => unreachable!(),
+        };
+        let mut schema = if self.wrapper == Wrapper::Vec {
+            format!("{}: [{}]", self.predicate, ty)
+        } else {
+            format!("{}: {}", self.predicate, ty)
+        };
+
+        if !self.indexes.is_empty() {
+            schema.push_str(&format!(" @index({})", self.indexes.join(", ")));
+        }
+        for (set, directive) in &[
+            (self.upsert, "@upsert"),
+            (self.reverse, "@reverse"),
+            (self.count, "@count"),
+            (self.lang, "@lang"),
+        ] {
+            if *set {
+                schema.push(' ');
+                schema.push_str(directive);
+            }
+        }
+        schema.push_str(" .");
+        schema
+    }
+
+    fn selection(&self) -> TokenStream2 {
+        let predicate = &self.predicate;
+        if !self.edge {
+            return quote! { selection.push(#predicate.to_string()); };
+        }
+
+        let inner = &self.inner;
+        quote! {
+            if depth > 0 {
+                selection.push(format!(
+                    "{} {{ {} }}",
+                    #predicate,
+                    <#inner as ::dgraph_rs::node::DgraphNode>::selection_depth(depth - 1),
+                ));
+            }
+        }
+    }
+
+    fn serialize(&self) -> TokenStream2 {
+        let ident = &self.ident;
+        let predicate = &self.predicate;
+        let time = !self.edge && is_system_time(&self.inner);
+
+        let value = |v: TokenStream2| match self.wrapper {
+            _ if !time => v,
+            Wrapper::Vec => quote! { &::dgraph_rs::node::__private::Rfc3339List(#v) },
+            _ => quote! { &::dgraph_rs::node::__private::Rfc3339(#v) },
+        };
+
+        if self.wrapper == Wrapper::Option {
+            let v = value(quote! { v });
+            quote! {
+                if let Some(v) = &self.#ident {
+                    map.serialize_entry(#predicate, #v)?;
+                }
+            }
+        } else {
+            let v = value(quote! { &self.#ident });
+            quote! { map.serialize_entry(#predicate, #v)?; }
+        }
+    }
+
+    /// The field of the struct `Deserialize` is derived for
+    fn deserialize_field(&self) -> TokenStream2 {
+        let ident = &self.ident;
+        let ty = &self.ty;
+
+        if self.skip {
+            return quote! {
+                #[serde(skip)]
+                #ident: #ty,
+            };
+        }
+
+        let predicate = &self.predicate;
+        let mut attrs = vec![quote! { rename = #predicate }];
+        if self.wrapper != Wrapper::None {
+            attrs.push(quote! { default });
+        }
+
+        let with = match self.wrapper {
+            _ if self.edge => None,
+            Wrapper::None if is_system_time(&self.inner) => Some("::dgraph_rs::datetime::rfc3339"),
+            Wrapper::Option if is_system_time(&self.inner) => Some("::dgraph_rs::datetime::rfc3339::option"),
+            Wrapper::Vec if is_system_time(&self.inner) => Some("::dgraph_rs::datetime::rfc3339::vec"),
+            _ => None,
+        };
+        if let Some(with) = with {
+            attrs.push(quote! { with = #with });
+        }
+
+        let deserialize_with = match self.wrapper {
+            _ if !self.edge => None,
+            Wrapper::None => Some("::dgraph_rs::node::__private::one_or_first"),
+            Wrapper::Option => Some("::dgraph_rs::node::__private::optional_one_or_first"),
+            Wrapper::Vec => None,
+        };
+        if let Some(deserialize_with) = deserialize_with {
+            attrs.push(quote! { deserialize_with = #deserialize_with });
+        }
+
+        quote! {
+            #[serde(#(#attrs),*)]
+            #ident: #ty,
+        }
+    }
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    if !input.generics.params.is_empty() {
+        return error(&input.generics, "DgraphNode can't be derived for generic types");
+    }
+
+    let named = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => return error(name, "DgraphNode needs a struct with named fields"),
+        },
+        _ => return error(name, "DgraphNode can only be derived for structs"),
+    };
+
+    let mut type_name = name.to_string();
+    for meta in dgraph_attrs(&input.attrs)? {
+        match &meta {
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("type") => {
+                type_name = lit_str(&nv.lit)?;
+            }
+            meta => return error(meta, "unknown dgraph attribute"),
+        }
+    }
+
+    let fields = named.iter().map(Field::parse).collect::<syn::Result<Vec<_>>>()?;
+    if fields.iter().filter(|f| f.uid).count() > 1 {
+        return error(name, "only one field can be the uid");
+    }
+
+    let uid = fields.iter().find(|f| f.uid);
+    let predicates: Vec<_> = fields.iter().filter(|f| !f.uid && !f.skip).collect();
+
+    let mut schema: Vec<_> = predicates.iter().map(|f| f.schema()).collect();
+    schema.push(format!("type {} {{", type_name));
+    schema.extend(predicates.iter().map(|f| format!("  {}", f.predicate)));
+    schema.push("}".to_string());
+    let schema = schema.join("\n") + "\n";
+
+    let selections = predicates.iter().map(|f| f.selection());
+
+    let serialize_uid = uid.map(|f| {
+        let ident = &f.ident;
+        if f.wrapper == Wrapper::Option {
+            quote! {
+                if let Some(uid) = &self.#ident {
+                    map.serialize_entry("uid", uid)?;
+                }
+            }
+        } else {
+            quote! { map.serialize_entry("uid", &self.#ident)?; }
+        }
+    });
+    let serialize_fields = predicates.iter().map(|f| f.serialize());
+
+    let deserialize_fields = fields.iter().map(|f| {
+        if f.uid {
+            let ident = &f.ident;
+            let ty = &f.ty;
+            let default = if f.wrapper == Wrapper::None { quote! {} } else { quote! { , default } };
+            quote! {
+                #[serde(rename = "uid" #default)]
+                #ident: #ty,
+            }
+        } else {
+            f.deserialize_field()
+        }
+    });
+    let idents: Vec<_> = fields.iter().map(|f| &f.ident).collect();
+
+    Ok(quote! {
+        impl ::dgraph_rs::node::DgraphNode for #name {
+            const TYPE: &'static str = #type_name;
+
+            fn selection_depth(depth: usize) -> String {
+                let mut selection: Vec<String> = vec!["uid".to_string()];
+                #(#selections)*
+                selection.join(" ")
+            }
+
+            fn schema() -> String {
+                #schema.to_string()
+            }
+        }
+
+        const _: () = {
+            use ::dgraph_rs::node::__private::serde;
+            use serde::ser::SerializeMap;
+
+            impl serde::Serialize for #name {
+                fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                    let mut map = serializer.serialize_map(None)?;
+                    #serialize_uid
+                    map.serialize_entry("dgraph.type", #type_name)?;
+                    #(#serialize_fields)*
+                    map.end()
+                }
+            }
+
+            #[derive(serde::Deserialize)]
+            #[serde(crate = "::dgraph_rs::node::__private::serde")]
+            struct Node {
+                #(#deserialize_fields)*
+            }
+
+            impl<'de> serde::Deserialize<'de> for #name {
+                fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                    let node = Node::deserialize(deserializer)?;
+                    Ok(#name {
+                        #(#idents: node.#idents),*
+                    })
+                }
+            }
+        };
+    })
+}
@@ -0,0 +1,150 @@
+use dgraph_rs::schema::Schema;
+use dgraph_rs::value::Uid;
+use dgraph_rs::DgraphNode;
+use serde_json::json;
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, PartialEq, DgraphNode)]
+#[dgraph(type = "File")]
+struct File {
+    uid: Option<Uid>,
+    #[dgraph(index(hash), upsert)]
+    node_key: String,
+    #[dgraph(predicate = "file_path", index(exact, trigram))]
+    path: Option<String>,
+    #[dgraph(type = "int")]
+    size: u64,
+}
+
+#[derive(Debug, PartialEq, DgraphNode)]
+struct Process {
+    #[dgraph(uid)]
+    id: Option<Uid>,
+    #[dgraph(index(hash), upsert)]
+    node_key: String,
+    created: SystemTime,
+    terminated: Option<SystemTime>,
+    arguments: Vec<String>,
+    #[dgraph(edge, reverse)]
+    bin_file: Option<File>,
+    #[dgraph(edge, count)]
+    children: Vec<Process>,
+    #[dgraph(skip)]
+    local: bool,
+}
+
+#[derive(Debug, PartialEq, DgraphNode)]
+struct Job {
+    uid: Option<Uid>,
+    runs: Vec<SystemTime>,
+}
+
+#[test]
+fn test_schema() {
+    assert_eq!(File::TYPE, "File");
+    assert_eq!(
+        File::schema(),
+        "node_key: string @index(hash) @upsert .\n\
+         file_path: string @index(exact, trigram) .\n\
+         size: int .\n\
+         type File {\n  node_key\n  file_path\n  size\n}\n",
+    );
+
+    assert_eq!(Process::TYPE, "Process");
+    assert_eq!(
+        Process::schema(),
+        "node_key: string @index(hash) @upsert .\n\
+         created: datetime .\n\
+         terminated: datetime .\n\
+         arguments: [string] .\n\
+         bin_file: uid @reverse .\n\
+         children: [uid] @count .\n\
+         type Process {\n  node_key\n  created\n  terminated\n  arguments\n  bin_file\n  children\n}\n",
+    );
+
+    let schema = Schema::parse(&Process::schema()).unwrap();
+    schema.validate().unwrap();
+    assert_eq!(schema.to_string(), Process::schema());
+}
+
+#[test]
+fn test_selection() {
+    assert_eq!(File::selection(), "uid node_key file_path size");
+    assert_eq!(
+        Process::selection_depth(1),
+        "uid node_key created terminated arguments \
+         bin_file { uid node_key file_path size } \
+         children { uid node_key created terminated arguments }",
+    );
+    assert_eq!(Process::selection_depth(0), "uid node_key created terminated arguments");
+}
+
+#[test]
+fn test_serde() {
+    let file = File { uid: None, node_key: "f".to_string(), path: None, size: 10 };
+    assert_eq!(
+        serde_json::to_value(&file).unwrap(),
+        json!({"dgraph.type": "File", "node_key": "f", "size": 10}),
+    );
+
+    let process = Process {
+        id: Some(Uid(0x2a)),
+        node_key: "p".to_string(),
+        created: UNIX_EPOCH + Duration::from_secs(1_500_000_000),
+        terminated: None,
+        arguments: vec!["-v".to_string()],
+        bin_file: Some(file),
+        children: vec![],
+        local: false,
+    };
+    let json = serde_json::to_value(&process).unwrap();
+    assert_eq!(
+        json,
+        json!({
+            "uid": "0x2a",
+            "dgraph.type": "Process",
+            "node_key": "p",
+            "created": "2017-07-14T02:40:00Z",
+            "arguments": ["-v"],
+            "bin_file": {"dgraph.type": "File", "node_key": "f", "size": 10},
+            "children": [],
+        }),
+    );
+    assert_eq!(serde_json::from_value::<Process>(json).unwrap(), process);
+
+    // Query results return uid edges as lists, and leave out empty predicates
+    let process: Process = serde_json::from_value(json!({
+        "uid": "0x1",
+        "node_key": "p",
+        "created": "2017-07-14T02:40:00Z",
+        "bin_file": [{"uid": "0x2", "node_key": "f", "file_path": "/bin/sh", "size": 1}],
+    }))
+    .unwrap();
+    assert_eq!(process.id, Some(Uid(1)));
+    assert!(process.arguments.is_empty());
+    assert!(process.children.is_empty());
+    let bin_file = process.bin_file.unwrap();
+    assert_eq!(bin_file.uid, Some(Uid(2)));
+    assert_eq!(bin_file.path.as_deref(), Some("/bin/sh"));
+}
+
+#[test]
+fn test_datetime_list() {
+    assert_eq!(Job::schema(), "runs: [datetime] .\ntype Job {\n  runs\n}\n");
+
+    let job = Job {
+        uid: Some(Uid(1)),
+        runs: vec![UNIX_EPOCH, UNIX_EPOCH + Duration::from_millis(1_500_000_000_500)],
+    };
+    let json = serde_json::to_value(&job).unwrap();
+    assert_eq!(
+        json,
+        json!({
+            "uid": "0x1",
+            "dgraph.type": "Job",
+            "runs": ["1970-01-01T00:00:00Z", "2017-07-14T02:40:00.5Z"],
+        }),
+    );
+    assert_eq!(serde_json::from_value::<Job>(json).unwrap(), job);
+}
@@ -0,0 +1,224 @@
+use crate::protos::api_grpc;
+
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use rand::{Rng, SeedableRng};
+
+/// A single Alpha connection along with the number of requests currently in flight against it.
+pub struct Endpoint {
+    client: api_grpc::DgraphClient,
+    in_flight: AtomicUsize,
+}
+
+impl Endpoint {
+    pub fn new(client: api_grpc::DgraphClient) -> Self {
+        Self {
+            client,
+            in_flight: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn client(&self) -> &api_grpc::DgraphClient {
+        &self.client
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::SeqCst)
+    }
+
+    /// Marks a request as in flight until the returned guard is dropped
+    pub(crate) fn start_request(&self) -> InFlightGuard<'_> {
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+        InFlightGuard { endpoint: self }
+    }
+}
+
+pub(crate) struct InFlightGuard<'a> {
+    endpoint: &'a Endpoint,
+}
+
+impl<'a> Drop for InFlightGuard<'a> {
+    fn drop(&mut self) {
+        self.endpoint.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Chooses which endpoint a new transaction is sent to.
+///
+/// `endpoints` is never empty, and the returned index must be in bounds.
+pub trait Balancer: Send + Sync {
+    fn pick(&self, endpoints: &[Endpoint]) -> usize;
+}
+
+/// Cycles through the endpoints in order. This is the default strategy.
+#[derive(Default)]
+pub struct RoundRobin {
+    next: AtomicUsize,
+}
+
+impl RoundRobin {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Balancer for RoundRobin {
+    fn pick(&self, endpoints: &[Endpoint]) -> usize {
+        self.next.fetch_add(1, Ordering::Relaxed) % endpoints.len()
+    }
+}
+
+/// Picks an endpoint uniformly at random.
+pub struct Random {
+    rng: Mutex<rand_xoshiro::Xoroshiro128Plus>,
+}
+
+impl Random {
+    pub fn new() -> Self {
+        Self::with_seed(rand::thread_rng().gen())
+    }
+
+    pub fn with_seed(seed: [u8; 16]) -> Self {
+        Self {
+            rng: Mutex::new(rand_xoshiro::Xoroshiro128Plus::from_seed(seed)),
+        }
+    }
+}
+
+impl Default for Random {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Balancer for Random {
+    fn pick(&self, endpoints: &[Endpoint]) -> usize {
+        let mut rng = self.rng.lock().unwrap();
+        rng.gen_range(0, endpoints.len())
+    }
+}
+
+/// Picks the endpoint with the fewest requests in flight.
+///
+/// Ties are broken round-robin, so an idle cluster still spreads its load.
+#[derive(Default)]
+pub struct LeastInFlight {
+    next: AtomicUsize,
+}
+
+impl LeastInFlight {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Balancer for LeastInFlight {
+    fn pick(&self, endpoints: &[Endpoint]) -> usize {
+        let offset = self.next.fetch_add(1, Ordering::Relaxed);
+        (0..endpoints.len())
+            .map(|i| (offset + i) % endpoints.len())
+            .min_by_key(|i| endpoints[*i].in_flight())
+            .unwrap()
+    }
+}
+
+/// Picks endpoints at random, proportionally to their weight.
+///
+/// `weights[i]` applies to the i'th endpoint passed to `DgraphClient`. Endpoints
+/// without a weight default to 1, and a weight of 0 means the endpoint is never picked
+/// unless every endpoint has a weight of 0.
+pub struct Weighted {
+    weights: Vec<u32>,
+    rng: Mutex<rand_xoshiro::Xoroshiro128Plus>,
+}
+
+impl Weighted {
+    pub fn new(weights: Vec<u32>) -> Self {
+        Self {
+            weights,
+            rng: Mutex::new(rand_xoshiro::Xoroshiro128Plus::from_seed(rand::thread_rng().gen())),
+        }
+    }
+
+    fn weight(&self, i: usize) -> u64 {
+        self.weights.get(i).copied().unwrap_or(1) as u64
+    }
+}
+
+impl Balancer for Weighted {
+    fn pick(&self, endpoints: &[Endpoint]) -> usize {
+        let total: u64 = (0..endpoints.len()).map(|i| self.weight(i)).sum();
+        if total == 0 {
+            return 0;
+        }
+
+        let mut target = self.rng.lock().unwrap().gen_range(0, total);
+        for i in 0..endpoints.len() {
+            let weight = self.weight(i);
+            if target < weight {
+                return i;
+            }
+            target -= weight;
+        }
+
+        unreachable!()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use grpc::{ClientStub, Client, ClientConf};
+    use std::sync::Arc;
+
+    fn endpoints(n: usize) -> Vec<Endpoint> {
+        (0..n)
+            .map(|_| {
+                let client = Client::new_plain("localhost", 9080, ClientConf::default())
+                    .expect("Failed to initialize client stub");
+                Endpoint::new(api_grpc::DgraphClient::with_client(Arc::new(client)))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_round_robin() {
+        let endpoints = endpoints(3);
+        let balancer = RoundRobin::new();
+
+        let picks: Vec<usize> = (0..6).map(|_| balancer.pick(&endpoints)).collect();
+        assert_eq!(picks, vec![0, 1, 2, 0, 1, 2]);
+    }
+
+    #[test]
+    fn test_least_in_flight() {
+        let endpoints = endpoints(3);
+        let balancer = LeastInFlight::new();
+
+        let _a = endpoints[0].start_request();
+        let _b = endpoints[2].start_request();
+        for _ in 0..3 {
+            assert_eq!(balancer.pick(&endpoints), 1);
+        }
+
+        drop(_b);
+        assert_eq!(endpoints[2].in_flight(), 0);
+        assert_ne!(balancer.pick(&endpoints), 0);
+    }
+
+    #[test]
+    fn test_weighted() {
+        let endpoints = endpoints(3);
+        let balancer = Weighted::new(vec![0, 3]);
+
+        let mut counts = [0; 3];
+        for _ in 0..1000 {
+            counts[balancer.pick(&endpoints)] += 1;
+        }
+
+        assert_eq!(counts[0], 0);
+        assert!(counts[1] > counts[2]);
+    }
+}
@@ -0,0 +1,299 @@
+use crate::DgraphClient;
+use crate::balancer::{Balancer, RoundRobin};
+use crate::errors::DgraphError;
+use crate::protos::api_grpc;
+
+use grpc::{ClientConf, ClientStub};
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::sync::Arc;
+use std::time::Duration;
+use tls_api::{TlsConnector, TlsConnectorBuilder};
+
+pub const DEFAULT_PORT: u16 = 9080;
+
+/// The address of a single Alpha, parsed from `host`, `host:port` or `dgraph://host:port`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EndpointAddr {
+    pub host: String,
+    pub port: u16,
+}
+
+impl EndpointAddr {
+    pub fn parse(s: &str) -> Result<Self, DgraphError> {
+        let invalid = || DgraphError::InvalidEndpoint(s.to_string());
+
+        let addr = match s.find("://") {
+            Some(i) if &s[..i] == "dgraph" => &s[i + 3..],
+            Some(_) => return Err(invalid()),
+            None => s,
+        };
+        let addr = addr.trim_end_matches('/');
+
+        // IPv6 addresses must be bracketed, as in [::1]:9080
+        let (host, port) = if let Some(bracketed) = addr.strip_prefix('[') {
+            let end = bracketed.find(']').ok_or_else(invalid)?;
+            let port = match &bracketed[end + 1..] {
+                "" => None,
+                rest => Some(rest.strip_prefix(':').ok_or_else(invalid)?),
+            };
+            (&bracketed[..end], port)
+        } else {
+            let (host, port) = match addr.find(':') {
+                Some(i) => (&addr[..i], Some(&addr[i + 1..])),
+                None => (addr, None),
+            };
+            if port.is_some_and(|port| port.contains(':')) {
+                return Err(invalid());
+            }
+            (host, port)
+        };
+
+        if host.is_empty() || host.contains(['/', '@', '?', '[', ']']) {
+            return Err(invalid());
+        }
+
+        let port = match port {
+            Some(port) => port.parse().map_err(|_| invalid())?,
+            None => DEFAULT_PORT,
+        };
+
+        Ok(Self { host: host.to_string(), port })
+    }
+
+    fn socket_addr(&self) -> Result<SocketAddr, DgraphError> {
+        (self.host.as_str(), self.port)
+            .to_socket_addrs()
+            .map_err(|e| DgraphError::from(grpc::Error::Io(e)))?
+            .next()
+            .ok_or_else(|| DgraphError::InvalidEndpoint(format!("{}:{}", self.host, self.port)))
+    }
+}
+
+type Connect = Box<dyn FnOnce(&[EndpointAddr], &ClientConf) -> Result<Vec<grpc::Client>, DgraphError>>;
+type Configure<B> = Box<dyn FnOnce(&mut B) -> tls_api::Result<()>>;
+
+/// TLS settings for `DgraphClientBuilder::tls`, for any `tls_api` backend
+pub struct TlsOptions<C: TlsConnector> {
+    root_certificates: Vec<tls_api::Certificate>,
+    verify_hostname: bool,
+    domain: Option<String>,
+    configure: Option<Configure<C::Builder>>,
+}
+
+impl<C: TlsConnector> TlsOptions<C> {
+    pub fn new() -> Self {
+        Self {
+            root_certificates: Vec::new(),
+            verify_hostname: true,
+            domain: None,
+            configure: None,
+        }
+    }
+
+    /// Trusts an additional CA, such as the one generated by `dgraph cert`
+    pub fn root_certificate(mut self, cert: tls_api::Certificate) -> Self {
+        self.root_certificates.push(cert);
+        self
+    }
+
+    pub fn verify_hostname(mut self, verify: bool) -> Self {
+        self.verify_hostname = verify;
+        self
+    }
+
+    /// The name to verify the server certificates against, when it differs from the
+    /// endpoint host
+    pub fn domain(mut self, domain: impl Into<String>) -> Self {
+        self.domain = Some(domain.into());
+        self
+    }
+
+    /// Configures the backend specific connector builder. `tls_api` has no portable way to
+    /// set a client certificate, so mutual TLS is set up here through `underlying_mut`.
+    pub fn configure(
+        mut self,
+        configure: impl FnOnce(&mut C::Builder) -> tls_api::Result<()> + 'static,
+    ) -> Self {
+        self.configure = Some(Box::new(configure));
+        self
+    }
+
+    fn build_connector(self) -> tls_api::Result<C> {
+        let mut builder = C::builder()?;
+
+        if C::supports_alpn() {
+            builder.set_alpn_protocols(&[b"h2"])?;
+        }
+        builder.set_verify_hostname(self.verify_hostname)?;
+        for cert in self.root_certificates {
+            builder.add_root_certificate(cert)?;
+        }
+        if let Some(configure) = self.configure {
+            configure(&mut builder)?;
+        }
+
+        builder.build()
+    }
+}
+
+impl<C: TlsConnector> Default for TlsOptions<C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Builds a `DgraphClient` from endpoint addresses.
+///
+/// ```ignore
+/// let dg = DgraphClient::builder()
+///     .endpoint("dgraph://alpha-0:9080")
+///     .endpoint("alpha-1:9080")
+///     .build()?;
+/// ```
+pub struct DgraphClientBuilder {
+    endpoints: Vec<String>,
+    conf: ClientConf,
+    balancer: Box<dyn FnOnce(Vec<api_grpc::DgraphClient>) -> DgraphClient>,
+    tls: Option<Connect>,
+    timeout: Option<Duration>,
+}
+
+impl DgraphClientBuilder {
+    pub fn new() -> Self {
+        Self {
+            endpoints: Vec::new(),
+            conf: ClientConf::default(),
+            balancer: Box::new(|dc| DgraphClient::with_balancer(dc, RoundRobin::new())),
+            tls: None,
+            timeout: None,
+        }
+    }
+
+    pub fn endpoint(mut self, endpoint: impl Into<String>) -> Self {
+        self.endpoints.push(endpoint.into());
+        self
+    }
+
+    pub fn endpoints<S: Into<String>>(mut self, endpoints: impl IntoIterator<Item=S>) -> Self {
+        self.endpoints.extend(endpoints.into_iter().map(Into::into));
+        self
+    }
+
+    /// The gRPC client configuration used for every endpoint
+    pub fn conf(mut self, conf: ClientConf) -> Self {
+        self.conf = conf;
+        self
+    }
+
+    /// The default timeout for every request, see `DgraphClient::default_timeout`
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn balancer(mut self, balancer: impl Balancer + 'static) -> Self {
+        self.balancer = Box::new(move |dc| DgraphClient::with_balancer(dc, balancer));
+        self
+    }
+
+    /// Connects to every endpoint over TLS, using the `tls_api` backend `C`
+    pub fn tls<C: TlsConnector>(mut self, options: TlsOptions<C>) -> Self {
+        self.tls = Some(Box::new(move |endpoints: &[EndpointAddr], conf: &ClientConf| {
+            let domain = options.domain.clone();
+            let connector = Arc::new(options.build_connector().map_err(DgraphError::TlsError)?);
+
+            endpoints.iter()
+                .map(|endpoint| {
+                    let domain = domain.clone().unwrap_or_else(|| endpoint.host.clone());
+                    let tls = httpbis::ClientTlsOption::Tls(domain, connector.clone());
+
+                    Ok(grpc::Client::new_expl(&endpoint.socket_addr()?, &endpoint.host, tls, conf.clone())?)
+                })
+                .collect()
+        }));
+        self
+    }
+
+    pub fn build(self) -> Result<DgraphClient, DgraphError> {
+        if self.endpoints.is_empty() {
+            return Err(DgraphError::InvalidEndpoint(String::new()));
+        }
+
+        let endpoints = self.endpoints.iter()
+            .map(|endpoint| EndpointAddr::parse(endpoint))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let clients = match self.tls {
+            Some(connect) => connect(&endpoints, &self.conf)?,
+            None => endpoints.iter()
+                .map(|endpoint| grpc::Client::new_plain(&endpoint.host, endpoint.port, self.conf.clone()))
+                .collect::<Result<Vec<_>, _>>()?,
+        };
+
+        let dc = clients.into_iter()
+            .map(|client| api_grpc::DgraphClient::with_client(Arc::new(client)))
+            .collect();
+
+        let client = (self.balancer)(dc);
+        match self.timeout {
+            Some(timeout) => Ok(client.default_timeout(timeout)),
+            None => Ok(client),
+        }
+    }
+}
+
+impl Default for DgraphClientBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(host: &str, port: u16) -> EndpointAddr {
+        EndpointAddr { host: host.to_string(), port }
+    }
+
+    #[test]
+    fn test_parse_endpoint() {
+        assert_eq!(EndpointAddr::parse("localhost").unwrap(), addr("localhost", 9080));
+        assert_eq!(EndpointAddr::parse("alpha-1:9180").unwrap(), addr("alpha-1", 9180));
+        assert_eq!(EndpointAddr::parse("dgraph://alpha-1:9180/").unwrap(), addr("alpha-1", 9180));
+        assert_eq!(EndpointAddr::parse("[::1]:9080").unwrap(), addr("::1", 9080));
+        assert_eq!(EndpointAddr::parse("dgraph://[::1]").unwrap(), addr("::1", 9080));
+
+        assert!(EndpointAddr::parse("http://localhost:9080").is_err());
+        assert!(EndpointAddr::parse("localhost:port").is_err());
+        assert!(EndpointAddr::parse("dgraph://localhost:9080/db").is_err());
+        assert!(EndpointAddr::parse(":9080").is_err());
+        assert!(EndpointAddr::parse("[::1]junk").is_err());
+        assert!(EndpointAddr::parse("[::1]9080").is_err());
+        assert!(EndpointAddr::parse("[::1]:").is_err());
+        assert!(EndpointAddr::parse("[::1").is_err());
+        assert!(EndpointAddr::parse("::1").is_err());
+        assert!(EndpointAddr::parse("fe80::1:9080").is_err());
+    }
+
+    #[test]
+    fn test_build() {
+        let dg = DgraphClient::builder()
+            .endpoints(vec!["localhost:9080", "dgraph://localhost:9081"])
+            .build()
+            .expect("Failed to build client");
+        assert_eq!(dg.endpoints().len(), 2);
+
+        assert!(DgraphClient::builder().build().is_err());
+
+        // The stub backend fails to create a connector
+        let res = DgraphClient::builder()
+            .endpoint("localhost")
+            .tls(TlsOptions::<tls_api_stub::TlsConnector>::new())
+            .build();
+        match res {
+            Err(DgraphError::TlsError(_)) => (),
+            _ => panic!("Expected a TlsError"),
+        }
+    }
+}
@@ -0,0 +1,449 @@
+//! Conversions between times and the encodings Dgraph uses for `datetime` predicates.
+//!
+//! `SystemTime` always converts to and from `api::Value`, and `rfc3339` writes it in the format
+//! Dgraph expects in JSON. With the `chrono` feature, chrono's `DateTime`, `NaiveDateTime` and
+//! `NaiveDate` convert too, keeping their offset from UTC.
+//!
+//! Internally times are handled as seconds since the Unix epoch, nanoseconds, and an optional
+//! offset from UTC in minutes, where `None` means UTC.
+use crate::errors::DgraphError;
+use crate::protos::api;
+
+use std::convert::TryFrom;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Seconds between January 1, year 1 (Go's internal epoch) and the Unix epoch
+const UNIX_TO_INTERNAL: i64 = 62_135_596_800;
+
+const SECS_PER_DAY: i64 = 86_400;
+
+pub(crate) fn from_system_time(t: SystemTime) -> (i64, u32) {
+    match t.duration_since(UNIX_EPOCH) {
+        Ok(d) => (d.as_secs() as i64, d.subsec_nanos()),
+        Err(e) => {
+            let d = e.duration();
+            match d.subsec_nanos() {
+                0 => (-(d.as_secs() as i64), 0),
+                nanos => (-(d.as_secs() as i64) - 1, 1_000_000_000 - nanos),
+            }
+        }
+    }
+}
+
+pub(crate) fn to_system_time(unix_secs: i64, nanos: u32) -> SystemTime {
+    if unix_secs >= 0 {
+        UNIX_EPOCH + Duration::new(unix_secs as u64, nanos)
+    } else {
+        UNIX_EPOCH - Duration::new(unix_secs.unsigned_abs(), 0) + Duration::new(0, nanos)
+    }
+}
+
+/// Encodes a time the way Go's `time.Time.MarshalBinary` does, which is what Dgraph expects
+/// in `datetime_val` and in datetime facets
+pub(crate) fn encode_binary(unix_secs: i64, nanos: u32, offset_minutes: Option<i16>) -> Vec<u8> {
+    let mut enc = Vec::with_capacity(15);
+    enc.push(1);
+    enc.extend_from_slice(&(unix_secs + UNIX_TO_INTERNAL).to_be_bytes());
+    enc.extend_from_slice(&(nanos as i32).to_be_bytes());
+    enc.extend_from_slice(&offset_minutes.unwrap_or(-1).to_be_bytes());
+    enc
+}
+
+/// Decodes the output of Go's `time.Time.MarshalBinary`
+pub(crate) fn decode_binary(enc: &[u8]) -> Option<(i64, u32, Option<i16>)> {
+    // Version 2 appends the seconds of the zone offset, which are always zero for
+    // the offsets Dgraph produces
+    match (enc.first(), enc.len()) {
+        (Some(1), 15) | (Some(2), 16) => (),
+        _ => return None,
+    }
+
+    let mut secs = [0; 8];
+    secs.copy_from_slice(&enc[1..9]);
+    let mut nanos = [0; 4];
+    nanos.copy_from_slice(&enc[9..13]);
+    let offset = i16::from_be_bytes([enc[13], enc[14]]);
+
+    let secs = i64::from_be_bytes(secs) - UNIX_TO_INTERNAL;
+    let nanos = i32::from_be_bytes(nanos);
+    if !(0..1_000_000_000).contains(&nanos) {
+        return None;
+    }
+
+    Some((secs, nanos as u32, if offset == -1 { None } else { Some(offset) }))
+}
+
+// Howard Hinnant's days_from_civil
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let yoe = year - era * 400;
+    let month = month as i64;
+    let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+// Howard Hinnant's civil_from_days
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = if days >= 0 { days } else { days - 146_096 } / 146_097;
+    let doe = days - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Formats a time as RFC3339, with nanoseconds if they are not zero
+pub(crate) fn format_rfc3339(unix_secs: i64, nanos: u32, offset_minutes: Option<i16>) -> String {
+    let offset = offset_minutes.unwrap_or(0) as i64;
+    let local = unix_secs + offset * 60;
+
+    let (year, month, day) = civil_from_days(local.div_euclid(SECS_PER_DAY));
+    let secs_of_day = local.rem_euclid(SECS_PER_DAY);
+
+    let mut s = format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        year, month, day, secs_of_day / 3600, secs_of_day / 60 % 60, secs_of_day % 60,
+    );
+
+    if nanos != 0 {
+        let frac = format!("{:09}", nanos);
+        s.push('.');
+        s.push_str(frac.trim_end_matches('0'));
+    }
+
+    match offset_minutes {
+        None => s.push('Z'),
+        Some(offset) => {
+            let sign = if offset < 0 { '-' } else { '+' };
+            let offset = offset.abs();
+            s.push_str(&format!("{}{:02}:{:02}", sign, offset / 60, offset % 60));
+        }
+    }
+
+    s
+}
+
+/// Parses the datetime formats Dgraph accepts: `2006`, `2006-01`, `2006-01-02`, and
+/// `2006-01-02T15:04:05` with optional fractional seconds and zone. Times without a zone
+/// are UTC.
+pub(crate) fn parse_rfc3339(s: &str) -> Option<(i64, u32, Option<i16>)> {
+    fn num<T: std::str::FromStr>(s: &str, len: usize) -> Option<T> {
+        if s.len() != len || !s.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        s.parse().ok()
+    }
+
+    let (date, time) = match s.find(&['T', 't'][..]) {
+        Some(i) => (&s[..i], Some(&s[i + 1..])),
+        None => (s, None),
+    };
+
+    let mut parts = date.split('-');
+    let year: i64 = num(parts.next()?, 4)?;
+    let month: u32 = parts.next().map(|m| num(m, 2)).unwrap_or(Some(1))?;
+    let day: u32 = parts.next().map(|d| num(d, 2)).unwrap_or(Some(1))?;
+    if parts.next().is_some() || !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) {
+        return None;
+    }
+
+    let days = days_from_civil(year, month, day);
+
+    let time = match time {
+        Some(time) => time,
+        None => return Some((days * SECS_PER_DAY, 0, None)),
+    };
+
+    let (time, offset) = if let Some(time) = time.strip_suffix(|c| c == 'Z' || c == 'z') {
+        (time, None)
+    } else if let Some(i) = time.rfind(&['+', '-'][..]) {
+        let zone = &time[i + 1..];
+        let hours: i16 = num(zone.get(..2)?, 2)?;
+        let minutes: i16 = num(zone.get(3..)?, 2)?;
+        if zone.as_bytes().get(2) != Some(&b':') || hours > 23 || minutes > 59 {
+            return None;
+        }
+        let offset = hours * 60 + minutes;
+        (&time[..i], Some(if time.as_bytes()[i] == b'-' { -offset } else { offset }))
+    } else {
+        (time, None)
+    };
+
+    let (time, nanos) = match time.find('.') {
+        Some(i) => {
+            let frac = &time[i + 1..];
+            if frac.is_empty() || frac.len() > 9 || !frac.bytes().all(|b| b.is_ascii_digit()) {
+                return None;
+            }
+            (&time[..i], format!("{:0<9}", frac).parse().ok()?)
+        }
+        None => (time, 0),
+    };
+
+    let mut parts = time.split(':');
+    let hour: i64 = num(parts.next()?, 2)?;
+    let minute: i64 = num(parts.next()?, 2)?;
+    let second: i64 = num(parts.next()?, 2)?;
+    if parts.next().is_some() || hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+
+    let local = days * SECS_PER_DAY + hour * 3600 + minute * 60 + second;
+    let unix_secs = local - offset.unwrap_or(0) as i64 * 60;
+
+    Some((unix_secs, nanos, offset))
+}
+
+fn decode_value(value: &api::Value) -> Result<(i64, u32, Option<i16>), DgraphError> {
+    let enc = if value.has_datetime_val() {
+        value.get_datetime_val()
+    } else if value.has_date_val() {
+        value.get_date_val()
+    } else {
+        return Err(DgraphError::InvalidDateTime(format!("Not a datetime value: {:?}", value)));
+    };
+
+    decode_binary(enc).ok_or_else(|| DgraphError::InvalidDateTime(format!("Invalid encoding {:?}", enc)))
+}
+
+impl TryFrom<&api::Value> for SystemTime {
+    type Error = DgraphError;
+
+    fn try_from(value: &api::Value) -> Result<Self, Self::Error> {
+        let (secs, nanos, _) = decode_value(value)?;
+        Ok(to_system_time(secs, nanos))
+    }
+}
+
+/// Serializes a `SystemTime` as an RFC3339 string, for `datetime` predicates in JSON
+/// mutations and query results.
+///
+/// ```ignore
+/// #[derive(Serialize, Deserialize)]
+/// struct Process {
+///     #[serde(with = "dgraph_rs::datetime::rfc3339")]
+///     created: SystemTime,
+/// }
+/// ```
+pub mod rfc3339 {
+    use serde::de::{self, Deserialize, Deserializer};
+    use serde::Serializer;
+    use std::time::SystemTime;
+
+    pub fn serialize<S: Serializer>(t: &SystemTime, serializer: S) -> Result<S::Ok, S::Error> {
+        let (secs, nanos) = super::from_system_time(*t);
+        serializer.serialize_str(&super::format_rfc3339(secs, nanos, None))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<SystemTime, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        let (secs, nanos, _) = super::parse_rfc3339(&s)
+            .ok_or_else(|| de::Error::custom(format!("invalid datetime {:?}", s)))?;
+        Ok(super::to_system_time(secs, nanos))
+    }
+
+    /// The same, for `Option<SystemTime>`
+    pub mod option {
+        use serde::{Deserialize, Deserializer, Serializer};
+        use std::time::SystemTime;
+
+        pub fn serialize<S: Serializer>(t: &Option<SystemTime>, serializer: S) -> Result<S::Ok, S::Error> {
+            match t {
+                Some(t) => super::serialize(t, serializer),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<SystemTime>, D::Error> {
+            #[derive(Deserialize)]
+            struct Wrapper(#[serde(with = "super")] SystemTime);
+
+            Ok(Option::<Wrapper>::deserialize(deserializer)?.map(|Wrapper(t)| t))
+        }
+    }
+
+    /// The same, for `Vec<SystemTime>`
+    pub mod vec {
+        use serde::ser::SerializeSeq;
+        use serde::{Deserialize, Deserializer, Serializer};
+        use std::time::SystemTime;
+
+        pub fn serialize<S: Serializer>(ts: &[SystemTime], serializer: S) -> Result<S::Ok, S::Error> {
+            let mut seq = serializer.serialize_seq(Some(ts.len()))?;
+            for t in ts {
+                let (secs, nanos) = super::super::from_system_time(*t);
+                seq.serialize_element(&super::super::format_rfc3339(secs, nanos, None))?;
+            }
+            seq.end()
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<SystemTime>, D::Error> {
+            #[derive(Deserialize)]
+            struct Wrapper(#[serde(with = "super")] SystemTime);
+
+            Ok(Vec::<Wrapper>::deserialize(deserializer)?.into_iter().map(|Wrapper(t)| t).collect())
+        }
+    }
+}
+
+#[cfg(feature = "chrono")]
+mod chrono_impls {
+    use super::*;
+
+    use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, Offset, TimeZone, Utc};
+
+    /// The offset is kept, except for UTC which is encoded as Go's UTC location
+    impl<Tz: TimeZone> From<DateTime<Tz>> for api::Value {
+        fn from(v: DateTime<Tz>) -> Self {
+            let offset = (v.offset().fix().local_minus_utc() / 60) as i16;
+
+            let mut value = api::Value::new();
+            value.set_datetime_val(encode_binary(
+                v.timestamp(),
+                v.timestamp_subsec_nanos(),
+                if offset == 0 { None } else { Some(offset) },
+            ));
+            value
+        }
+    }
+
+    /// Naive times are taken to be in UTC, as Dgraph does for times without a zone
+    impl From<NaiveDateTime> for api::Value {
+        fn from(v: NaiveDateTime) -> Self {
+            Utc.from_utc_datetime(&v).into()
+        }
+    }
+
+    impl From<NaiveDate> for api::Value {
+        fn from(v: NaiveDate) -> Self {
+            Utc.from_utc_datetime(&v.and_hms_opt(0, 0, 0).unwrap()).into()
+        }
+    }
+
+    impl TryFrom<&api::Value> for DateTime<FixedOffset> {
+        type Error = DgraphError;
+
+        fn try_from(value: &api::Value) -> Result<Self, Self::Error> {
+            let (secs, nanos, offset) = decode_value(value)?;
+            let invalid = || DgraphError::InvalidDateTime(format!("Out of range: {:?}", value));
+
+            let offset = FixedOffset::east_opt(offset.unwrap_or(0) as i32 * 60).ok_or_else(invalid)?;
+            offset.timestamp_opt(secs, nanos).single().ok_or_else(invalid)
+        }
+    }
+
+    impl TryFrom<&api::Value> for DateTime<Utc> {
+        type Error = DgraphError;
+
+        fn try_from(value: &api::Value) -> Result<Self, Self::Error> {
+            DateTime::<FixedOffset>::try_from(value).map(|t| t.with_timezone(&Utc))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_binary_round_trip() {
+        // Matches time.Unix(1, 5).UTC().MarshalBinary() in Go
+        let enc = encode_binary(1, 5, None);
+        assert_eq!(enc, vec![1, 0, 0, 0, 14, 119, 145, 247, 1, 0, 0, 0, 5, 255, 255]);
+        assert_eq!(decode_binary(&enc), Some((1, 5, None)));
+
+        let enc = encode_binary(-86_400, 0, Some(-300));
+        assert_eq!(decode_binary(&enc), Some((-86_400, 0, Some(-300))));
+
+        assert_eq!(decode_binary(&enc[..14]), None);
+    }
+
+    #[test]
+    fn test_system_time() {
+        let t = UNIX_EPOCH - Duration::new(1, 250);
+        assert_eq!(from_system_time(t), (-2, 999_999_750));
+        assert_eq!(to_system_time(-2, 999_999_750), t);
+    }
+
+    #[test]
+    fn test_value() {
+        let t = UNIX_EPOCH + Duration::new(1_136_214_245, 500);
+        assert_eq!(SystemTime::try_from(&api::Value::from(t)).unwrap(), t);
+        assert!(SystemTime::try_from(&api::Value::from(5)).is_err());
+    }
+
+    #[test]
+    fn test_serde_rfc3339() {
+        #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+        struct Process {
+            #[serde(with = "rfc3339")]
+            created: SystemTime,
+            #[serde(with = "rfc3339::option", default)]
+            exited: Option<SystemTime>,
+        }
+
+        let process = Process {
+            created: UNIX_EPOCH + Duration::new(1_136_214_245, 250_000_000),
+            exited: None,
+        };
+        let json = serde_json::to_value(&process).unwrap();
+        assert_eq!(json, serde_json::json!({"created": "2006-01-02T15:04:05.25Z", "exited": null}));
+        assert_eq!(serde_json::from_value::<Process>(json).unwrap(), process);
+
+        let process: Process = serde_json::from_value(serde_json::json!({
+            "created": "2006-01-02T15:04:05-07:00",
+            "exited": "2006-01-03",
+        })).unwrap();
+        assert_eq!(process.created, UNIX_EPOCH + Duration::from_secs(1_136_239_445));
+        assert_eq!(process.exited, Some(UNIX_EPOCH + Duration::from_secs(1_136_246_400)));
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn test_chrono() {
+        use chrono::{DateTime, FixedOffset, NaiveDate, Utc};
+
+        let t = DateTime::parse_from_rfc3339("2006-01-02T15:04:05.25-07:00").unwrap();
+        let value = api::Value::from(t);
+        assert_eq!(decode_binary(value.get_datetime_val()), Some((1_136_239_445, 250_000_000, Some(-420))));
+        assert_eq!(DateTime::<FixedOffset>::try_from(&value).unwrap(), t);
+        assert_eq!(DateTime::<Utc>::try_from(&value).unwrap(), t.with_timezone(&Utc));
+
+        let date = NaiveDate::from_ymd_opt(2006, 1, 2).unwrap();
+        let value = api::Value::from(date);
+        assert_eq!(decode_binary(value.get_datetime_val()), Some((1_136_160_000, 0, None)));
+    }
+
+    #[test]
+    fn test_rfc3339() {
+        assert_eq!(parse_rfc3339("1970-01-01T00:00:01Z"), Some((1, 0, None)));
+        assert_eq!(parse_rfc3339("2006-01-02"), Some((1_136_160_000, 0, None)));
+        assert_eq!(parse_rfc3339("2006"), Some((1_136_073_600, 0, None)));
+        assert_eq!(
+            parse_rfc3339("2006-01-02T15:04:05.25-07:00"),
+            Some((1_136_239_445, 250_000_000, Some(-420))),
+        );
+
+        assert_eq!(format_rfc3339(1_136_239_445, 250_000_000, Some(-420)), "2006-01-02T15:04:05.25-07:00");
+        assert_eq!(format_rfc3339(-1, 0, None), "1969-12-31T23:59:59Z");
+
+        assert_eq!(parse_rfc3339("2006-02-30"), None);
+        assert_eq!(parse_rfc3339("2006-01-02T25:00:00Z"), None);
+        assert_eq!(parse_rfc3339("yesterday"), None);
+    }
+}
@@ -1,13 +1,102 @@
+use crate::version::ServerVersion;
+
 #[derive(Debug)]
 pub enum DgraphError {
     Finished,
     EmptyTransaction,
     ReadOnly,
     StartTsMismatch,
+    /// The transaction conflicted with another and was aborted by the server
+    Aborted(grpc::Error),
+    /// The server rejected the query or mutation as malformed
+    InvalidQuery { message: String, source: grpc::Error },
+    Unauthenticated(grpc::Error),
+    PermissionDenied(grpc::Error),
+    /// The endpoint could not be reached, or is not currently serving requests
+    Unavailable(grpc::Error),
+    DeadlineExceeded(grpc::Error),
+    /// Any other gRPC failure
     GrpcError(grpc::Error),
+    ProtobufError(protobuf::ProtobufError),
+    NotLoggedIn,
+    InvalidVersion(String),
+    UnsupportedVersion { endpoint: usize, version: ServerVersion },
+    MissingBlock(String),
+    DeserializeError { block: Option<String>, source: serde_json::Error },
+    SerializeError(serde_json::Error),
+    InvalidEndpoint(String),
+    TlsError(tls_api::Error),
+    /// The request did not complete within the client side timeout
+    Timeout(std::time::Duration),
+    InvalidUpsert(String),
+    InvalidRdf {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    InvalidNQuad(String),
+    InvalidFacet(String),
+    InvalidDateTime(String),
+    InvalidName(String),
+    /// A query function argument that can't be written into the query
+    InvalidArgument(String),
+    InvalidVars(String),
+    InvalidUid(String),
+    InvalidSchemaText {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    InvalidSchema(String),
+    /// The live schema lacks something that `Schema::check_includes` requires
+    SchemaMismatch(String),
     Unknown,
 }
 
+// Dgraph reports query syntax errors with an Unknown status, so they are recognized by message
+const INVALID_QUERY_MESSAGES: &[&str] = &[
+    "while lexing",
+    "while parsing",
+    "Syntax Error",
+    "Invalid query",
+];
+
+impl DgraphError {
+    /// Whether the server aborted the transaction due to a conflict
+    pub fn is_aborted(&self) -> bool {
+        matches!(self, DgraphError::Aborted(_))
+    }
+
+    /// Whether the whole transaction can safely be retried from the start. This is the
+    /// policy question, which `is_aborted` answers for now: an `Unavailable` error may come
+    /// from a connection that dropped after the server applied a commit, so only aborts are
+    /// retryable. Callers deciding whether to retry should use this, so they pick up any
+    /// error that is later found to be safe to retry.
+    pub fn is_retryable(&self) -> bool {
+        self.is_aborted()
+    }
+
+    fn grpc_error(&self) -> Option<&grpc::Error> {
+        match self {
+            DgraphError::Aborted(e)
+            | DgraphError::InvalidQuery { source: e, .. }
+            | DgraphError::Unauthenticated(e)
+            | DgraphError::PermissionDenied(e)
+            | DgraphError::Unavailable(e)
+            | DgraphError::DeadlineExceeded(e)
+            | DgraphError::GrpcError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+fn server_message(e: &grpc::Error) -> String {
+    match e {
+        grpc::Error::GrpcMessage(e) => e.grpc_message.clone(),
+        e => e.to_string(),
+    }
+}
+
 impl std::fmt::Display for DgraphError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -15,18 +104,138 @@ impl std::fmt::Display for DgraphError {
             DgraphError::EmptyTransaction => write!(f, "EmptyTransaction"),
             DgraphError::ReadOnly => write!(f, "Can not mutate, set to read only"),
             DgraphError::StartTsMismatch => write!(f, "StartTsMismatch"),
-            DgraphError::GrpcError(_) => write!(f, "GrpcError"),
+            DgraphError::Aborted(e) => write!(f, "Transaction aborted: {}", server_message(e)),
+            DgraphError::InvalidQuery { message, .. } => write!(f, "Invalid query: {}", message),
+            DgraphError::Unauthenticated(e) => write!(f, "Unauthenticated: {}", server_message(e)),
+            DgraphError::PermissionDenied(e) => write!(f, "Permission denied: {}", server_message(e)),
+            DgraphError::Unavailable(e) => write!(f, "Dgraph unavailable: {}", server_message(e)),
+            DgraphError::DeadlineExceeded(e) => write!(f, "Deadline exceeded: {}", server_message(e)),
+            DgraphError::GrpcError(e) => write!(f, "GrpcError: {}", server_message(e)),
+            DgraphError::ProtobufError(_) => write!(f, "ProtobufError"),
+            DgraphError::NotLoggedIn => write!(f, "Can not refresh access token, not logged in"),
+            DgraphError::InvalidVersion(tag) => write!(f, "Invalid server version: {}", tag),
+            DgraphError::UnsupportedVersion { endpoint, version } => write!(
+                f, "Endpoint {} runs unsupported Dgraph version {}, {} or higher is required",
+                endpoint, version, crate::version::MIN_SUPPORTED_VERSION,
+            ),
+            DgraphError::MissingBlock(block) => write!(f, "Query block {} missing from response", block),
+            DgraphError::DeserializeError { block: Some(block), source } => write!(
+                f, "Failed to deserialize query block {}: {}", block, source,
+            ),
+            DgraphError::DeserializeError { block: None, source } => write!(
+                f, "Failed to deserialize response: {}", source,
+            ),
+            DgraphError::SerializeError(e) => write!(f, "Failed to serialize mutation: {}", e),
+            DgraphError::InvalidEndpoint(endpoint) => write!(f, "Invalid endpoint: {:?}", endpoint),
+            DgraphError::TlsError(e) => write!(f, "TlsError: {}", e),
+            DgraphError::Timeout(timeout) => write!(f, "Request timed out after {:?}", timeout),
+            DgraphError::InvalidUpsert(message) => write!(f, "Invalid upsert: {}", message),
+            DgraphError::InvalidRdf { line, column, message } => write!(
+                f, "Invalid RDF at line {}, column {}: {}", line, column, message,
+            ),
+            DgraphError::InvalidNQuad(message) => write!(f, "Invalid NQuad: {}", message),
+            DgraphError::InvalidFacet(message) => write!(f, "Invalid facet: {}", message),
+            DgraphError::InvalidDateTime(message) => write!(f, "Invalid datetime: {}", message),
+            DgraphError::InvalidName(name) => write!(f, "Invalid name in query: {:?}", name),
+            DgraphError::InvalidArgument(message) => write!(f, "Invalid query argument: {}", message),
+            DgraphError::InvalidVars(message) => write!(f, "Invalid query variables: {}", message),
+            DgraphError::InvalidUid(uid) => write!(f, "Invalid uid: {:?}", uid),
+            DgraphError::InvalidSchemaText { line, column, message } => write!(
+                f, "Invalid schema at line {}, column {}: {}", line, column, message,
+            ),
+            DgraphError::InvalidSchema(message) => write!(f, "Invalid schema: {}", message),
+            DgraphError::SchemaMismatch(message) => write!(f, "Schema mismatch: {}", message),
             DgraphError::Unknown => write!(f, "UnknownError"),
         }
     }
 }
 
 impl std::error::Error for DgraphError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        if let Some(e) = self.grpc_error() {
+            return Some(e);
+        }
 
+        match self {
+            DgraphError::ProtobufError(e) => Some(e),
+            DgraphError::DeserializeError { source, .. } => Some(source),
+            DgraphError::SerializeError(e) => Some(e),
+            DgraphError::TlsError(e) => Some(e),
+            _ => None,
+        }
+    }
 }
 
 impl From<grpc::Error> for DgraphError {
     fn from(e: grpc::Error) -> DgraphError {
-        DgraphError::GrpcError(e)
+        let (status, message) = match &e {
+            grpc::Error::GrpcMessage(m) => (m.grpc_status, m.grpc_message.as_str()),
+            grpc::Error::Io(_) | grpc::Error::Http(_) => return DgraphError::Unavailable(e),
+            _ => return DgraphError::GrpcError(e),
+        };
+
+        if status == grpc::GrpcStatus::Aborted as i32 || message.contains("Transaction has been aborted") {
+            return DgraphError::Aborted(e);
+        }
+
+        if status == grpc::GrpcStatus::Argument as i32
+            || INVALID_QUERY_MESSAGES.iter().any(|m| message.contains(m)) {
+            return DgraphError::InvalidQuery { message: message.to_string(), source: e };
+        }
+
+        match status {
+            s if s == grpc::GrpcStatus::Unauthenticated as i32 => DgraphError::Unauthenticated(e),
+            s if s == grpc::GrpcStatus::PermissionDenied as i32 => DgraphError::PermissionDenied(e),
+            s if s == grpc::GrpcStatus::Unavailable as i32 => DgraphError::Unavailable(e),
+            s if s == grpc::GrpcStatus::DeadlineExceeded as i32 => DgraphError::DeadlineExceeded(e),
+            _ => DgraphError::GrpcError(e),
+        }
+    }
+}
+
+impl From<protobuf::ProtobufError> for DgraphError {
+    fn from(e: protobuf::ProtobufError) -> DgraphError {
+        DgraphError::ProtobufError(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grpc_message(status: grpc::GrpcStatus, message: &str) -> grpc::Error {
+        grpc::Error::GrpcMessage(grpc::GrpcMessageError {
+            grpc_status: status as i32,
+            grpc_message: message.to_string(),
+        })
+    }
+
+    #[test]
+    fn test_classify_grpc_errors() {
+        let e = DgraphError::from(grpc_message(grpc::GrpcStatus::Aborted, "Transaction has been aborted. Please retry"));
+        assert!(e.is_aborted());
+        assert!(e.is_retryable());
+        assert_eq!(e.to_string(), "Transaction aborted: Transaction has been aborted. Please retry");
+
+        let e = DgraphError::from(grpc_message(grpc::GrpcStatus::Unknown, "while lexing {q0(func: has(}: Unexpected EOF"));
+        match &e {
+            DgraphError::InvalidQuery { message, .. } => assert!(message.starts_with("while lexing")),
+            e => panic!("Expected InvalidQuery, got {:?}", e),
+        }
+        assert!(!e.is_retryable());
+        assert!(std::error::Error::source(&e).is_some());
+
+        let e = DgraphError::from(grpc_message(grpc::GrpcStatus::Unavailable, "connection refused"));
+        assert!(matches!(e, DgraphError::Unavailable(_)));
+        assert!(!e.is_retryable());
+
+        let e = DgraphError::from(grpc_message(grpc::GrpcStatus::DeadlineExceeded, "context deadline exceeded"));
+        assert!(!e.is_retryable());
+
+        let e = DgraphError::from(grpc_message(grpc::GrpcStatus::Unauthenticated, "Token is expired"));
+        match e {
+            DgraphError::Unauthenticated(_) => (),
+            e => panic!("Expected Unauthenticated, got {:?}", e),
+        }
     }
-}
\ No newline at end of file
+}
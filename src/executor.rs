@@ -0,0 +1,20 @@
+use futures::future::BoxFuture;
+
+/// Spawns background work, such as discarding transactions that were dropped without being
+/// committed. Implemented for any `Fn(BoxFuture<'static, ()>)`, so a runtime's spawn function
+/// can be passed directly:
+///
+/// ```ignore
+/// let dg = DgraphClient::new(clients).discard_on_drop(|fut| { async_std::task::spawn(fut); });
+/// ```
+pub trait Executor: Send + Sync {
+    fn spawn(&self, future: BoxFuture<'static, ()>);
+}
+
+impl<F> Executor for F
+    where F: Fn(BoxFuture<'static, ()>) + Send + Sync,
+{
+    fn spawn(&self, future: BoxFuture<'static, ()>) {
+        (self)(future)
+    }
+}
@@ -0,0 +1,401 @@
+use crate::datetime;
+use crate::errors::DgraphError;
+use crate::protos::api;
+
+use serde::de::{self, IgnoredAny, MapAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::time::SystemTime;
+
+/// A typed facet value, encoded the way Dgraph stores it in `api::Facet::value`
+#[derive(Debug, Clone, PartialEq)]
+pub enum FacetValue {
+    String(String),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    DateTime(SystemTime),
+}
+
+impl FacetValue {
+    pub fn val_type(&self) -> api::Facet_ValType {
+        match self {
+            FacetValue::String(_) => api::Facet_ValType::STRING,
+            FacetValue::Int(_) => api::Facet_ValType::INT,
+            FacetValue::Float(_) => api::Facet_ValType::FLOAT,
+            FacetValue::Bool(_) => api::Facet_ValType::BOOL,
+            FacetValue::DateTime(_) => api::Facet_ValType::DATETIME,
+        }
+    }
+
+    /// Encodes the value as Dgraph does: little endian for numbers, a single byte for
+    /// bools and Go's `time.Time.MarshalBinary` format for datetimes
+    pub fn encode(&self) -> Vec<u8> {
+        match self {
+            FacetValue::String(s) => s.as_bytes().to_vec(),
+            FacetValue::Int(i) => i.to_le_bytes().to_vec(),
+            FacetValue::Float(f) => f.to_bits().to_le_bytes().to_vec(),
+            FacetValue::Bool(b) => vec![*b as u8],
+            FacetValue::DateTime(t) => {
+                let (secs, nanos) = datetime::from_system_time(*t);
+                datetime::encode_binary(secs, nanos, None)
+            }
+        }
+    }
+
+    pub fn decode(val_type: api::Facet_ValType, value: &[u8]) -> Result<Self, DgraphError> {
+        let invalid = || DgraphError::InvalidFacet(format!("{:?} value {:?}", val_type, value));
+        let le_bytes = || {
+            let mut bytes = [0; 8];
+            if value.len() != 8 {
+                return Err(invalid());
+            }
+            bytes.copy_from_slice(value);
+            Ok(bytes)
+        };
+
+        let value = match val_type {
+            api::Facet_ValType::STRING => {
+                FacetValue::String(String::from_utf8(value.to_vec()).map_err(|_| invalid())?)
+            }
+            api::Facet_ValType::INT => FacetValue::Int(i64::from_le_bytes(le_bytes()?)),
+            api::Facet_ValType::FLOAT => FacetValue::Float(f64::from_bits(u64::from_le_bytes(le_bytes()?))),
+            api::Facet_ValType::BOOL => match value {
+                [0] => FacetValue::Bool(false),
+                [1] => FacetValue::Bool(true),
+                _ => return Err(invalid()),
+            },
+            api::Facet_ValType::DATETIME => {
+                let (secs, nanos, _) = datetime::decode_binary(value).ok_or_else(invalid)?;
+                FacetValue::DateTime(datetime::to_system_time(secs, nanos))
+            }
+        };
+        Ok(value)
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            FacetValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            FacetValue::Int(i) => Some(*i),
+            _ => None,
+        }
+    }
+
+    /// Ints are widened, since Dgraph returns whole floats as ints in JSON
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            FacetValue::Float(f) => Some(*f),
+            FacetValue::Int(i) => Some(*i as f64),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            FacetValue::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    /// The datetime, or a string parsed as an RFC 3339 datetime, as facets decoded from JSON
+    /// query results are
+    pub fn as_datetime(&self) -> Option<SystemTime> {
+        match self {
+            FacetValue::DateTime(t) => Some(*t),
+            FacetValue::String(s) => datetime::parse_rfc3339(s)
+                .map(|(secs, nanos, _)| datetime::to_system_time(secs, nanos)),
+            _ => None,
+        }
+    }
+}
+
+macro_rules! facet_value_from {
+    ($ty:ty, $variant:ident, $conv:expr) => {
+        impl From<$ty> for FacetValue {
+            fn from(v: $ty) -> Self {
+                FacetValue::$variant($conv(v))
+            }
+        }
+    };
+}
+
+facet_value_from!(String, String, |v| v);
+facet_value_from!(&str, String, str::to_string);
+facet_value_from!(i64, Int, |v| v);
+facet_value_from!(i32, Int, i64::from);
+facet_value_from!(u32, Int, i64::from);
+facet_value_from!(f64, Float, |v| v);
+facet_value_from!(f32, Float, f64::from);
+facet_value_from!(bool, Bool, |v| v);
+facet_value_from!(SystemTime, DateTime, |v| v);
+
+/// Builds an `api::Facet`, as in `NQuad::facet(("since", SystemTime::now()))`
+impl<K: Into<String>, V: Into<FacetValue>> From<(K, V)> for api::Facet {
+    fn from((key, value): (K, V)) -> Self {
+        let value = value.into();
+
+        let mut facet = api::Facet::new();
+        facet.key = key.into();
+        facet.val_type = value.val_type();
+        facet.value = value.encode();
+        facet
+    }
+}
+
+impl TryFrom<&api::Facet> for FacetValue {
+    type Error = DgraphError;
+
+    fn try_from(facet: &api::Facet) -> Result<Self, Self::Error> {
+        FacetValue::decode(facet.val_type, &facet.value)
+    }
+}
+
+/// Datetimes are serialized as RFC3339 strings, which is how Dgraph accepts them in JSON
+impl Serialize for FacetValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            FacetValue::String(s) => serializer.serialize_str(s),
+            FacetValue::Int(i) => serializer.serialize_i64(*i),
+            FacetValue::Float(f) => serializer.serialize_f64(*f),
+            FacetValue::Bool(b) => serializer.serialize_bool(*b),
+            FacetValue::DateTime(t) => {
+                let (secs, nanos) = datetime::from_system_time(*t);
+                serializer.serialize_str(&datetime::format_rfc3339(secs, nanos, None))
+            }
+        }
+    }
+}
+
+/// Query results carry no facet types, so strings in RFC3339 format are read as datetimes
+impl<'de> Deserialize<'de> for FacetValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(FacetValueVisitor)
+    }
+}
+
+struct FacetValueVisitor;
+
+impl<'de> Visitor<'de> for FacetValueVisitor {
+    type Value = FacetValue;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "a string, number or bool facet")
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<FacetValue, E> {
+        Ok(FacetValue::Bool(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<FacetValue, E> {
+        Ok(FacetValue::Int(v))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<FacetValue, E> {
+        match i64::try_from(v) {
+            Ok(i) => Ok(FacetValue::Int(i)),
+            Err(_) => Ok(FacetValue::Float(v as f64)),
+        }
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<FacetValue, E> {
+        Ok(FacetValue::Float(v))
+    }
+
+    // JSON results don't say which strings are datetimes, so they are all kept as strings
+    // and parsed by `as_datetime`
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<FacetValue, E> {
+        Ok(FacetValue::String(v.to_string()))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Entry {
+    Single(FacetValue),
+    // Facets on list predicates are keyed by the index of the value they belong to
+    List(HashMap<usize, FacetValue>),
+}
+
+/// The facets of a query result node, collected from its `predicate|facet` keys.
+///
+/// Meant to be flattened into the struct a node is decoded into:
+///
+/// ```ignore
+/// #[derive(Deserialize)]
+/// struct Person {
+///     name: String,
+///     #[serde(flatten)]
+///     facets: Facets,
+/// }
+///
+/// let since = person.facets.get("name", "since");
+/// ```
+///
+/// Facets on an edge are returned in the node the edge points to, still keyed by the edge
+/// predicate.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Facets {
+    facets: HashMap<String, HashMap<String, Entry>>,
+}
+
+impl Facets {
+    pub fn get(&self, predicate: &str, facet: &str) -> Option<&FacetValue> {
+        match self.facets.get(predicate)?.get(facet)? {
+            Entry::Single(value) => Some(value),
+            Entry::List(_) => None,
+        }
+    }
+
+    /// The facet of the `index`th value of a list predicate
+    pub fn get_at(&self, predicate: &str, facet: &str, index: usize) -> Option<&FacetValue> {
+        match self.facets.get(predicate)?.get(facet)? {
+            Entry::Single(_) => None,
+            Entry::List(values) => values.get(&index),
+        }
+    }
+
+    /// The names of the facets set on `predicate`
+    pub fn keys<'a>(&'a self, predicate: &str) -> impl Iterator<Item=&'a str> + 'a {
+        self.facets.get(predicate)
+            .into_iter()
+            .flat_map(|facets| facets.keys().map(String::as_str))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.facets.is_empty()
+    }
+}
+
+impl<'de> Deserialize<'de> for Facets {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(FacetsVisitor)
+    }
+}
+
+struct FacetsVisitor;
+
+impl<'de> Visitor<'de> for FacetsVisitor {
+    type Value = Facets;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "a query result node")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Facets, A::Error> {
+        let mut facets = Facets::default();
+
+        while let Some(key) = map.next_key::<String>()? {
+            let i = match key.find('|') {
+                Some(i) => i,
+                None => {
+                    map.next_value::<IgnoredAny>()?;
+                    continue;
+                }
+            };
+
+            let entry = match map.next_value::<ListOrSingle>()? {
+                ListOrSingle::Single(value) => Entry::Single(value),
+                ListOrSingle::List(values) => {
+                    let values = values.into_iter()
+                        .map(|(index, value)| {
+                            let index = index.parse().map_err(|_| de::Error::custom(
+                                format!("invalid list index {:?} for facet {}", index, key),
+                            ))?;
+                            Ok((index, value))
+                        })
+                        .collect::<Result<_, A::Error>>()?;
+                    Entry::List(values)
+                }
+            };
+
+            facets.facets
+                .entry(key[..i].to_string())
+                .or_default()
+                .insert(key[i + 1..].to_string(), entry);
+        }
+
+        Ok(facets)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ListOrSingle {
+    Single(FacetValue),
+    List(HashMap<String, FacetValue>),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn test_encode_facet() {
+        let facet = api::Facet::from(("weight", 0.5));
+        assert_eq!(facet.key, "weight");
+        assert_eq!(facet.val_type, api::Facet_ValType::FLOAT);
+        assert_eq!(facet.value, vec![0, 0, 0, 0, 0, 0, 224, 63]);
+
+        let facet = api::Facet::from(("rank", -2));
+        assert_eq!(facet.value, vec![254, 255, 255, 255, 255, 255, 255, 255]);
+
+        let since = UNIX_EPOCH + Duration::new(1, 5);
+        let facet = api::Facet::from(("since", since));
+        assert_eq!(facet.value, vec![1, 0, 0, 0, 14, 119, 145, 247, 1, 0, 0, 0, 5, 255, 255]);
+
+        for value in [FacetValue::from("a"), 7.into(), 1.5.into(), true.into(), since.into()] {
+            let facet = api::Facet::from(("key", value.clone()));
+            assert_eq!(FacetValue::try_from(&facet).unwrap(), value);
+        }
+
+        assert!(FacetValue::decode(api::Facet_ValType::INT, &[1, 2]).is_err());
+        assert!(FacetValue::decode(api::Facet_ValType::BOOL, &[2]).is_err());
+    }
+
+    #[test]
+    fn test_deserialize_facets() {
+        #[derive(Deserialize)]
+        struct Person {
+            name: String,
+            #[serde(flatten)]
+            facets: Facets,
+        }
+
+        let person: Person = serde_json::from_value(serde_json::json!({
+            "name": "Alice",
+            "name|origin": "french",
+            "name|since": "2006-01-02T15:04:05Z",
+            "friend|close": true,
+            "nickname|weight": {"0": 0.5, "1": 2},
+        })).unwrap();
+
+        assert_eq!(person.name, "Alice");
+        let facets = &person.facets;
+        assert_eq!(facets.get("name", "origin"), Some(&FacetValue::String("french".into())));
+        assert_eq!(facets.get("name", "origin").and_then(FacetValue::as_datetime), None);
+        assert_eq!(
+            facets.get("name", "since"),
+            Some(&FacetValue::String("2006-01-02T15:04:05Z".into())),
+        );
+        assert_eq!(
+            facets.get("name", "since").and_then(FacetValue::as_datetime),
+            Some(UNIX_EPOCH + Duration::from_secs(1_136_214_245)),
+        );
+        assert_eq!(facets.get("friend", "close").and_then(FacetValue::as_bool), Some(true));
+        assert_eq!(facets.get_at("nickname", "weight", 1).and_then(FacetValue::as_f64), Some(2.0));
+        assert_eq!(facets.get("nickname", "weight"), None);
+        assert_eq!(facets.get("name", "missing"), None);
+
+        let json = serde_json::to_value(FacetValue::DateTime(UNIX_EPOCH)).unwrap();
+        assert_eq!(json, serde_json::json!("1970-01-01T00:00:00Z"));
+    }
+}
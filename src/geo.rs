@@ -0,0 +1,357 @@
+//! Geo values for `geo` predicates.
+//!
+//! Geometries are sent as WKB in `api::Value::geo_val`, serialize to GeoJSON for JSON
+//! mutations, and decode from the GeoJSON Dgraph returns in query results.
+use crate::errors::DgraphError;
+use crate::protos::api;
+use crate::query::{Arg, Func};
+use crate::value::Geo;
+
+use serde::de::{self, Deserializer};
+use serde::{Deserialize, Serialize, Serializer};
+
+const WKB_POINT: u32 = 1;
+const WKB_POLYGON: u32 = 3;
+const WKB_MULTI_POLYGON: u32 = 6;
+
+/// A geometry that can be stored in a `geo` predicate
+pub trait Geometry {
+    /// Encodes the geometry as little endian WKB
+    fn to_wkb(&self) -> Vec<u8>;
+
+    /// The coordinates as an argument to Dgraph's geo functions, such as `[1.5, 2.0]`
+    fn coordinates(&self) -> Arg;
+}
+
+/// A point, with the longitude first as in GeoJSON
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Point {
+    pub lon: f64,
+    pub lat: f64,
+}
+
+impl Point {
+    pub fn new(lon: f64, lat: f64) -> Self {
+        Self { lon, lat }
+    }
+
+    fn write_wkb(&self, wkb: &mut Vec<u8>) {
+        wkb.extend_from_slice(&self.lon.to_le_bytes());
+        wkb.extend_from_slice(&self.lat.to_le_bytes());
+    }
+
+    fn coordinates_arg(&self) -> Arg {
+        Arg::List(vec![Arg::Float(self.lon), Arg::Float(self.lat)])
+    }
+}
+
+/// A polygon made of an exterior ring followed by any holes.
+///
+/// Rings are closed automatically, since Dgraph rejects rings whose last point is not their
+/// first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Polygon {
+    pub rings: Vec<Vec<Point>>,
+}
+
+impl Polygon {
+    pub fn new(exterior: impl Into<Vec<Point>>) -> Self {
+        Self { rings: vec![close(exterior.into())] }
+    }
+
+    pub fn hole(mut self, ring: impl Into<Vec<Point>>) -> Self {
+        self.rings.push(close(ring.into()));
+        self
+    }
+
+    fn write_wkb(&self, wkb: &mut Vec<u8>) {
+        header(wkb, WKB_POLYGON);
+        wkb.extend_from_slice(&(self.rings.len() as u32).to_le_bytes());
+        for ring in &self.rings {
+            wkb.extend_from_slice(&(ring.len() as u32).to_le_bytes());
+            for point in ring {
+                point.write_wkb(wkb);
+            }
+        }
+    }
+
+    fn coordinates_arg(&self) -> Arg {
+        Arg::List(self.rings.iter()
+            .map(|ring| Arg::List(ring.iter().map(Point::coordinates_arg).collect()))
+            .collect())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MultiPolygon {
+    pub polygons: Vec<Polygon>,
+}
+
+impl MultiPolygon {
+    pub fn new(polygons: impl Into<Vec<Polygon>>) -> Self {
+        Self { polygons: polygons.into() }
+    }
+}
+
+fn close(mut ring: Vec<Point>) -> Vec<Point> {
+    if let (Some(first), Some(last)) = (ring.first(), ring.last()) {
+        if first != last {
+            ring.push(*first);
+        }
+    }
+    ring
+}
+
+fn header(wkb: &mut Vec<u8>, kind: u32) {
+    // 1 marks little endian
+    wkb.push(1);
+    wkb.extend_from_slice(&kind.to_le_bytes());
+}
+
+impl Geometry for Point {
+    fn to_wkb(&self) -> Vec<u8> {
+        let mut wkb = Vec::with_capacity(21);
+        header(&mut wkb, WKB_POINT);
+        self.write_wkb(&mut wkb);
+        wkb
+    }
+
+    fn coordinates(&self) -> Arg {
+        self.coordinates_arg()
+    }
+}
+
+impl Geometry for Polygon {
+    fn to_wkb(&self) -> Vec<u8> {
+        let mut wkb = Vec::new();
+        self.write_wkb(&mut wkb);
+        wkb
+    }
+
+    fn coordinates(&self) -> Arg {
+        self.coordinates_arg()
+    }
+}
+
+impl Geometry for MultiPolygon {
+    fn to_wkb(&self) -> Vec<u8> {
+        let mut wkb = Vec::new();
+        header(&mut wkb, WKB_MULTI_POLYGON);
+        wkb.extend_from_slice(&(self.polygons.len() as u32).to_le_bytes());
+        for polygon in &self.polygons {
+            polygon.write_wkb(&mut wkb);
+        }
+        wkb
+    }
+
+    fn coordinates(&self) -> Arg {
+        Arg::List(self.polygons.iter().map(Polygon::coordinates_arg).collect())
+    }
+}
+
+macro_rules! geo_from {
+    ($ty:ty) => {
+        impl From<$ty> for Geo {
+            fn from(v: $ty) -> Self {
+                Geo(v.to_wkb())
+            }
+        }
+
+        impl From<$ty> for api::Value {
+            fn from(v: $ty) -> Self {
+                Geo::from(v).into()
+            }
+        }
+    };
+}
+
+geo_from!(Point);
+geo_from!(Polygon);
+geo_from!(MultiPolygon);
+
+/// `near(predicate, [lon, lat], distance)`, as text for a hand written query. See `Func::near`.
+pub fn near(predicate: &str, point: &Point, meters: f64) -> Result<String, DgraphError> {
+    Func::near(predicate, point, meters).build()
+}
+
+/// `within(predicate, polygon)`, as text for a hand written query. See `Func::within`.
+pub fn within(predicate: &str, polygon: &Polygon) -> Result<String, DgraphError> {
+    Func::within(predicate, polygon).build()
+}
+
+/// `contains(predicate, geometry)`, as text for a hand written query. See `Func::contains`.
+pub fn contains(predicate: &str, geometry: &impl Geometry) -> Result<String, DgraphError> {
+    Func::contains(predicate, geometry).build()
+}
+
+/// `intersects(predicate, geometry)`, as text for a hand written query. See
+/// `Func::intersects`.
+pub fn intersects(predicate: &str, geometry: &impl Geometry) -> Result<String, DgraphError> {
+    Func::intersects(predicate, geometry).build()
+}
+
+#[derive(Serialize, Deserialize)]
+struct GeoJson<C> {
+    #[serde(rename = "type")]
+    kind: String,
+    coordinates: C,
+}
+
+type PointCoordinates = [f64; 2];
+type PolygonCoordinates = Vec<Vec<PointCoordinates>>;
+
+impl From<PointCoordinates> for Point {
+    fn from([lon, lat]: PointCoordinates) -> Self {
+        Point::new(lon, lat)
+    }
+}
+
+fn polygon_coordinates(polygon: &Polygon) -> PolygonCoordinates {
+    polygon.rings.iter()
+        .map(|ring| ring.iter().map(|p| [p.lon, p.lat]).collect())
+        .collect()
+}
+
+fn polygon_from_coordinates(rings: PolygonCoordinates) -> Polygon {
+    Polygon {
+        rings: rings.into_iter()
+            .map(|ring| ring.into_iter().map(Point::from).collect())
+            .collect(),
+    }
+}
+
+fn serialize_geo_json<S: Serializer, C: Serialize>(
+    serializer: S,
+    kind: &str,
+    coordinates: C,
+) -> Result<S::Ok, S::Error> {
+    GeoJson { kind: kind.to_string(), coordinates }.serialize(serializer)
+}
+
+fn deserialize_geo_json<'de, D: Deserializer<'de>, C: Deserialize<'de>>(
+    deserializer: D,
+    kind: &str,
+) -> Result<C, D::Error> {
+    let geo_json = GeoJson::<C>::deserialize(deserializer)?;
+    if geo_json.kind != kind {
+        return Err(de::Error::custom(format!("expected a {}, found a {}", kind, geo_json.kind)));
+    }
+    Ok(geo_json.coordinates)
+}
+
+impl Serialize for Point {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_geo_json(serializer, "Point", [self.lon, self.lat])
+    }
+}
+
+impl<'de> Deserialize<'de> for Point {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_geo_json::<_, PointCoordinates>(deserializer, "Point").map(Point::from)
+    }
+}
+
+impl Serialize for Polygon {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_geo_json(serializer, "Polygon", polygon_coordinates(self))
+    }
+}
+
+impl<'de> Deserialize<'de> for Polygon {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_geo_json(deserializer, "Polygon").map(polygon_from_coordinates)
+    }
+}
+
+impl Serialize for MultiPolygon {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let coordinates: Vec<_> = self.polygons.iter().map(polygon_coordinates).collect();
+        serialize_geo_json(serializer, "MultiPolygon", coordinates)
+    }
+}
+
+impl<'de> Deserialize<'de> for MultiPolygon {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let polygons: Vec<PolygonCoordinates> = deserialize_geo_json(deserializer, "MultiPolygon")?;
+        Ok(MultiPolygon::new(polygons.into_iter().map(polygon_from_coordinates).collect::<Vec<_>>()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square() -> Polygon {
+        Polygon::new(vec![
+            Point::new(0.0, 0.0),
+            Point::new(1.0, 0.0),
+            Point::new(1.0, 1.0),
+            Point::new(0.0, 1.0),
+        ])
+    }
+
+    #[test]
+    fn test_wkb() {
+        let wkb = Point::new(1.0, 2.0).to_wkb();
+        assert_eq!(wkb, vec![
+            1, 1, 0, 0, 0,
+            0, 0, 0, 0, 0, 0, 240, 63,
+            0, 0, 0, 0, 0, 0, 0, 64,
+        ]);
+        assert_eq!(api::Value::from(Point::new(1.0, 2.0)).get_geo_val(), &wkb[..]);
+
+        let polygon = square();
+        assert_eq!(polygon.rings[0].len(), 5);
+
+        let wkb = polygon.to_wkb();
+        assert_eq!(&wkb[..13], &[1, 3, 0, 0, 0, 1, 0, 0, 0, 5, 0, 0, 0]);
+        assert_eq!(wkb.len(), 13 + 5 * 16);
+
+        let wkb = MultiPolygon::new(vec![square(), square()]).to_wkb();
+        assert_eq!(&wkb[..9], &[1, 6, 0, 0, 0, 2, 0, 0, 0]);
+        assert_eq!(wkb.len(), 9 + 2 * (13 + 5 * 16));
+    }
+
+    #[test]
+    fn test_geo_json() {
+        let point = Point::new(-122.5, 37.75);
+        let json = serde_json::to_value(point).unwrap();
+        assert_eq!(json, serde_json::json!({"type": "Point", "coordinates": [-122.5, 37.75]}));
+        assert_eq!(serde_json::from_value::<Point>(json).unwrap(), point);
+
+        let multi = MultiPolygon::new(vec![square()]);
+        let json = serde_json::to_value(&multi).unwrap();
+        assert_eq!(json["coordinates"][0][0][4], serde_json::json!([0.0, 0.0]));
+        assert_eq!(serde_json::from_value::<MultiPolygon>(json).unwrap(), multi);
+
+        assert!(serde_json::from_value::<Polygon>(serde_json::to_value(point).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_functions() {
+        let point = Point::new(-122.5, 37.75);
+        assert_eq!(near("loc", &point, 1000.0).unwrap(), "near(loc, [-122.5, 37.75], 1000.0)");
+        assert_eq!(contains("area", &point).unwrap(), "contains(area, [-122.5, 37.75])");
+        assert_eq!(
+            within("loc", &square()).unwrap(),
+            "within(loc, [[[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0], [0.0, 0.0]]])",
+        );
+        assert_eq!(
+            intersects("area", &MultiPolygon::new(vec![square()])).unwrap(),
+            "intersects(area, [[[[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0], [0.0, 0.0]]]])",
+        );
+
+        let invalid_argument = |res: Result<String, DgraphError>| match res {
+            Err(DgraphError::InvalidArgument(_)) => (),
+            res => panic!("Expected an InvalidArgument error, got {:?}", res),
+        };
+        invalid_argument(near("loc", &point, f64::NAN));
+        invalid_argument(near("loc", &Point::new(f64::INFINITY, 0.0), 1.0));
+        invalid_argument(contains("area", &Polygon::new(vec![Point::new(0.0, f64::NAN)])));
+
+        match near("loc) { uid } q(func: has(secret)", &point, 1.0) {
+            Err(DgraphError::InvalidName(_)) => (),
+            res => panic!("Expected an InvalidName error, got {:?}", res),
+        }
+    }
+}
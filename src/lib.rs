@@ -1,43 +1,116 @@
+extern crate bytes;
 extern crate futures;
 
 extern crate grpc;
 extern crate httpbis;
 extern crate protobuf;
+extern crate tls_api;
 extern crate serde;
 extern crate serde_json;
 
 use crate::protos::{api, api_grpc::{self, Dgraph}};
-//use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex};
 
 use futures::compat::Future01CompatExt;
 
+use balancer::{Balancer, Endpoint, RoundRobin};
+use builder::DgraphClientBuilder;
 use errors::DgraphError;
+use version::ServerVersion;
 use std::collections::HashMap;
-use rand::{Rng, SeedableRng};
-use rand::seq::SliceRandom;
-
+use std::time::Duration;
+use executor::Executor;
+use futures::FutureExt;
+use futures::future::BoxFuture;
+use mutation::{Assigned, MutationBatch};
+use retry::RetryPolicy;
+use schema::Schema;
+use serde::Serialize;
+use upsert::{ConditionalUpsert, UpsertResponse};
+use vars::Vars;
+
+pub use node::DgraphNode;
+#[cfg(feature = "derive")]
+pub use dgraph_derive::DgraphNode;
+use serde::de::DeserializeOwned;
+
+pub mod balancer;
+pub mod builder;
+pub mod datetime;
 pub mod errors;
+pub mod executor;
+pub mod facet;
+pub mod geo;
+pub mod mutation;
+pub mod node;
+pub mod nquad;
 pub mod protos;
-
-
+pub mod query;
+pub mod rdf;
+pub mod retry;
+pub mod schema;
+pub mod upsert;
+pub mod value;
+pub mod vars;
+pub mod version;
+
+mod response;
+mod timeout;
+
+
+/// A handle to a set of Dgraph endpoints. Cloning is cheap, and clones share the same
+/// connections, login and balancer.
+#[derive(Clone)]
 pub struct DgraphClient
 {
-    //    _jwt_mutex: Option<Arc<Mutex<api::Jwt>>>,
-    dc: Vec<api_grpc::DgraphClient>,
-    rng_seed: [u8; 16],
+    jwt: Arc<Mutex<Option<api::Jwt>>>,
+    endpoints: Arc<Vec<Endpoint>>,
+    balancer: Arc<dyn Balancer>,
+    drop_executor: Option<Arc<dyn Executor>>,
+    timeout: Option<Duration>,
 }
 
 impl DgraphClient
 {
     pub fn new(dc: Vec<api_grpc::DgraphClient>) -> Self {
+        Self::with_balancer(dc, RoundRobin::new())
+    }
+
+    pub fn with_balancer(dc: Vec<api_grpc::DgraphClient>, balancer: impl Balancer + 'static) -> Self {
         assert!(!dc.is_empty());
         Self {
-//            jwt_mutex: None,
-            rng_seed: rand::thread_rng().gen(),
-            dc,
+            jwt: Arc::new(Mutex::new(None)),
+            endpoints: Arc::new(dc.into_iter().map(Endpoint::new).collect()),
+            balancer: Arc::new(balancer),
+            drop_executor: None,
+            timeout: None,
         }
     }
 
+    pub fn builder() -> DgraphClientBuilder {
+        DgraphClientBuilder::new()
+    }
+
+    /// Sets the timeout for every request made through this client, unless overridden per
+    /// call. It is sent to the server as the gRPC deadline, and also enforced client side.
+    pub fn default_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Discards transactions that are dropped after mutating, without having been committed
+    /// or discarded, by spawning a best effort abort request on `executor`. Without this,
+    /// the server keeps such transactions open until they time out. Debug builds also print a
+    /// warning to stderr for each such transaction, so leaks show up during development.
+    pub fn discard_on_drop(mut self, executor: impl Executor + 'static) -> Self {
+        self.drop_executor = Some(Arc::new(executor));
+        self
+    }
+
+    pub fn endpoints(&self) -> &[Endpoint] {
+        &self.endpoints
+    }
+
     pub fn new_txn(&self) -> Txn {
         Txn {
             context: Default::default(),
@@ -45,7 +118,8 @@ impl DgraphClient
             read_only: false,
             best_effort: false,
             mutated: false,
-            dc: self.any_client(),
+            client: self.clone(),
+            endpoint: self.pick_endpoint(),
         }
     }
 
@@ -57,7 +131,8 @@ impl DgraphClient
             read_only: true,
             best_effort: false,
             mutated: false,
-            dc: self.any_client(),
+            client: self.clone(),
+            endpoint: self.pick_endpoint(),
         }
     }
 
@@ -69,27 +144,265 @@ impl DgraphClient
             read_only: true,
             best_effort: true,
             mutated: false,
-            dc: self.any_client(),
+            client: self.clone(),
+            endpoint: self.pick_endpoint(),
+        }
+    }
+
+    /// Logs in to an ACL enabled cluster. The access JWT is sent with every subsequent
+    /// request, and is refreshed automatically when the server reports that it has expired.
+    pub async fn login(&self, user: impl Into<String>, password: impl Into<String>) -> Result<(), DgraphError> {
+        self._login(
+            api::LoginRequest {
+                userid: user.into(),
+                password: password.into(),
+                ..Default::default()
+            }
+        ).await
+    }
+
+    async fn relogin(&self) -> Result<(), DgraphError> {
+        let refresh_token = match self.jwt.lock().unwrap().as_ref() {
+            Some(jwt) => jwt.refresh_jwt.clone(),
+            None => return Err(DgraphError::NotLoggedIn),
+        };
+
+        self._login(
+            api::LoginRequest {
+                refresh_token,
+                ..Default::default()
+            }
+        ).await
+    }
+
+    async fn _login(&self, req: api::LoginRequest) -> Result<(), DgraphError> {
+        let endpoint = self.any_endpoint();
+        let _in_flight = endpoint.start_request();
+
+        let mut options = grpc::RequestOptions::new();
+        if let Some(timeout) = self.timeout {
+            timeout::set_deadline(&mut options, timeout);
+        }
+
+        let res = timeout::with_timeout(self.timeout, async {
+            Ok(endpoint.client().login(options, req).join_metadata_result().compat().await?)
+        }).await?;
+
+        let jwt: api::Jwt = protobuf::parse_from_bytes(&res.1.json)?;
+        *self.jwt.lock().unwrap() = Some(jwt);
+
+        Ok(())
+    }
+
+    fn request_options(&self, timeout: Option<Duration>) -> grpc::RequestOptions {
+        let mut options = grpc::RequestOptions::new();
+
+        if let Some(timeout) = timeout {
+            timeout::set_deadline(&mut options, timeout);
+        }
+
+        if let Some(jwt) = self.jwt.lock().unwrap().as_ref() {
+            // gRPC metadata keys are case insensitive, and are sent lower case on the wire
+            options.metadata.add(
+                grpc::MetadataKey::from("accessjwt"),
+                bytes::Bytes::from(jwt.access_jwt.as_str()),
+            );
+        }
+
+        options
+    }
+
+    /// Makes a request with the current access JWT, logging in again and retrying once
+    /// if the token has expired
+    async fn call<T, F>(&self, timeout: Option<Duration>, call: F) -> Result<T, DgraphError>
+        where T: Send + 'static,
+              F: Fn(grpc::RequestOptions) -> grpc::SingleResponse<T>,
+    {
+        timeout::with_timeout(timeout, async {
+            let res = call(self.request_options(timeout)).join_metadata_result().compat().await;
+
+            let res = match res {
+                Err(ref e) if is_jwt_expired(e) => {
+                    self.relogin().await?;
+                    call(self.request_options(timeout)).join_metadata_result().compat().await?
+                }
+                res => res?,
+            };
+
+            Ok(res.1)
+        }).await
+    }
+
+    /// Returns the version of every endpoint, in the order they were passed to the client
+    pub async fn check_version(&self) -> Result<Vec<ServerVersion>, DgraphError> {
+        let mut versions = Vec::with_capacity(self.endpoints.len());
+
+        for endpoint in self.endpoints.iter() {
+            let _in_flight = endpoint.start_request();
+            let version = self.call(self.timeout, |options| {
+                endpoint.client().check_version(options, api::Check::new())
+            }).await?;
+
+            versions.push(version.tag.parse()?);
+        }
+
+        Ok(versions)
+    }
+
+    /// Fails if any endpoint runs a version older than `version::MIN_SUPPORTED_VERSION`.
+    /// Intended to be called once at startup.
+    pub async fn ensure_supported_version(&self) -> Result<(), DgraphError> {
+        for (endpoint, version) in self.check_version().await?.into_iter().enumerate() {
+            if !version.is_supported() {
+                return Err(DgraphError::UnsupportedVersion { endpoint, version });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Runs `f` in a new transaction and commits it, retrying with the default `RetryPolicy`
+    /// if the transaction is aborted due to a conflict. Other errors, including `Unavailable`
+    /// ones after which the commit may have been applied, are returned as is.
+    ///
+    /// ```ignore
+    /// let uid = dg.run_txn(|txn| async move {
+    ///     let assigned = txn.set(&process).await?;
+    ///     Ok(assigned.uid("_:process").map(String::from))
+    /// }.boxed()).await?;
+    /// ```
+    pub async fn run_txn<T, F>(&self, f: F) -> Result<T, DgraphError>
+        where F: for<'t> FnMut(&'t mut Txn) -> BoxFuture<'t, Result<T, DgraphError>>,
+    {
+        self.run_txn_with(RetryPolicy::default(), f).await
+    }
+
+    pub async fn run_txn_with<T, F>(&self, policy: RetryPolicy, mut f: F) -> Result<T, DgraphError>
+        where F: for<'t> FnMut(&'t mut Txn) -> BoxFuture<'t, Result<T, DgraphError>>,
+    {
+        let mut attempt = 0;
+        loop {
+            let mut txn = self.new_txn();
+
+            let res = match f(&mut txn).await {
+                Ok(value) => txn.commit_or_abort().await.map(|_| value),
+                Err(e) => {
+                    let _ = txn.discard().await;
+                    Err(e)
+                }
+            };
+
+            attempt += 1;
+            match res {
+                Err(DgraphError::Aborted(_)) if attempt < policy.max_attempts => {
+                    futures_timer::Delay::new(policy.backoff(attempt - 1)).await;
+                }
+                res => return res,
+            }
         }
     }
 
-    fn any_client(&self) -> &api_grpc::DgraphClient {
-        let mut rng = rand_xoshiro::Xoroshiro128Plus::from_seed(self.rng_seed);
+    pub async fn alter(&self, op: api::Operation) -> Result<api::Payload, DgraphError> {
+        let endpoint = self.any_endpoint();
+        let _in_flight = endpoint.start_request();
+
+        self.call(self.timeout, |options| endpoint.client().alter(options, op.clone())).await
+    }
+
+    /// Reads the current schema, with a `schema {}` query
+    pub async fn schema(&self) -> Result<Schema, DgraphError> {
+        self.new_read_only().query_as("schema {}").await
+    }
+
+    /// Validates `schema` and sets it
+    pub async fn alter_schema(&self, schema: &Schema) -> Result<api::Payload, DgraphError> {
+        schema.validate()?;
+        self.set_schema(schema.to_string()).await
+    }
+
+    /// Sets the schema for the predicates and type of a `DgraphNode`
+    pub async fn set_node_schema<T: DgraphNode>(&self) -> Result<api::Payload, DgraphError> {
+        self.set_schema(T::schema()).await
+    }
+
+    pub async fn set_schema(&self, schema: impl Into<String>) -> Result<api::Payload, DgraphError> {
+        self.alter(
+            api::Operation {
+                schema: schema.into(),
+                ..Default::default()
+            }
+        ).await
+    }
+
+    /// Drops all data and schema
+    pub async fn drop_all(&self) -> Result<api::Payload, DgraphError> {
+        self.alter(
+            api::Operation {
+                drop_op: api::Operation_DropOp::ALL,
+                ..Default::default()
+            }
+        ).await
+    }
+
+    /// Drops all data, but keeps the schema
+    pub async fn drop_data(&self) -> Result<api::Payload, DgraphError> {
+        self.alter(
+            api::Operation {
+                drop_op: api::Operation_DropOp::DATA,
+                ..Default::default()
+            }
+        ).await
+    }
 
-        self.dc.choose(&mut rng).unwrap()
+    pub async fn drop_attr(&self, attr: impl Into<String>) -> Result<api::Payload, DgraphError> {
+        self.alter(
+            api::Operation {
+                drop_op: api::Operation_DropOp::ATTR,
+                drop_value: attr.into(),
+                ..Default::default()
+            }
+        ).await
+    }
+
+    pub async fn drop_type(&self, type_name: impl Into<String>) -> Result<api::Payload, DgraphError> {
+        self.alter(
+            api::Operation {
+                drop_op: api::Operation_DropOp::TYPE,
+                drop_value: type_name.into(),
+                ..Default::default()
+            }
+        ).await
+    }
+
+    fn pick_endpoint(&self) -> usize {
+        self.balancer.pick(&self.endpoints)
+    }
+
+    fn any_endpoint(&self) -> &Endpoint {
+        &self.endpoints[self.pick_endpoint()]
     }
 }
 
-pub struct Txn<'a> {
+fn is_jwt_expired(e: &grpc::Error) -> bool {
+    match e {
+        grpc::Error::GrpcMessage(e) => e.grpc_message.contains("Token is expired"),
+        _ => false,
+    }
+}
+
+/// A transaction against a single endpoint. Transactions own a handle to their client, so
+/// they can be moved into spawned tasks.
+pub struct Txn {
     context: api::TxnContext,
     finished: bool,
     read_only: bool,
     best_effort: bool,
     mutated: bool,
-    dc: &'a api_grpc::DgraphClient,
+    client: DgraphClient,
+    endpoint: usize,
 }
 
-impl<'a> Txn<'a> {
+impl Txn {
     pub async fn query(&mut self, q: impl Into<String>) -> Result<api::Response, DgraphError> {
         self.query_with_vars(q, HashMap::new()).await
     }
@@ -99,51 +412,205 @@ impl<'a> Txn<'a> {
         q: impl Into<String>,
         vars: HashMap<String, String>,
     ) -> Result<api::Response, DgraphError> {
-        self._do(
-            api::Request {
-                query: q.into(),
-                start_ts: self.context.start_ts,
-                read_only: self.read_only,
-                best_effort: self.best_effort,
-                vars,
-                ..Default::default()
-            }
-        ).await
+        let req = self.query_request(q.into(), vars);
+        self._do(req, self.client.timeout).await
+    }
+
+    pub async fn query_timeout(&mut self, q: impl Into<String>, timeout: Duration) -> Result<api::Response, DgraphError> {
+        self.query_with_vars_timeout(q, HashMap::new(), timeout).await
+    }
+
+    pub async fn query_with_vars_timeout(
+        &mut self,
+        q: impl Into<String>,
+        vars: HashMap<String, String>,
+        timeout: Duration,
+    ) -> Result<api::Response, DgraphError> {
+        let req = self.query_request(q.into(), vars);
+        self._do(req, Some(timeout)).await
+    }
+
+    /// Queries with typed variables, declaring them in the query if it has no declaration of
+    /// its own. See `Vars::apply`.
+    pub async fn query_vars(&mut self, q: &str, vars: &Vars) -> Result<api::Response, DgraphError> {
+        let q = vars.apply(q)?;
+        self.query_with_vars(q, vars.to_map()).await
+    }
+
+    pub async fn query_vars_as<T: DeserializeOwned>(&mut self, q: &str, vars: &Vars) -> Result<T, DgraphError> {
+        self.query_vars(q, vars).await?.decode()
+    }
+
+    pub async fn query_as<T: DeserializeOwned>(&mut self, q: impl Into<String>) -> Result<T, DgraphError> {
+        self.query(q).await?.decode()
+    }
+
+    pub async fn query_with_vars_as<T: DeserializeOwned>(
+        &mut self,
+        q: impl Into<String>,
+        vars: HashMap<String, String>,
+    ) -> Result<T, DgraphError> {
+        self.query_with_vars(q, vars).await?.decode()
     }
 
     pub async fn mutate(&mut self, mu: api::Mutation) -> Result<api::Response, DgraphError> {
-        self._do(
-            api::Request {
-                start_ts: self.context.start_ts,
-                commit_now: mu.commit_now,
-                mutations: vec![mu].into(),
-                ..Default::default()
-            }
-        ).await
+        let req = self.mutate_request(mu);
+        self._do(req, self.client.timeout).await
     }
 
-    pub async fn upsert(&mut self, q: impl Into<String>, mut mu: api::Mutation) -> Result<api::Response, DgraphError> {
-        mu.commit_now = true;
-        self._do(
-            api::Request {
-                query: q.into(),
-                mutations: vec![mu].into(),
-                commit_now: true,
-                ..Default::default()
-            }
-        ).await
+    pub async fn mutate_timeout(&mut self, mu: api::Mutation, timeout: Duration) -> Result<api::Response, DgraphError> {
+        let req = self.mutate_request(mu);
+        self._do(req, Some(timeout)).await
+    }
+
+    /// Sends several mutations in a single request. Their `commit_now` is cleared, as with
+    /// `mutate_batch`, so the transaction is only committed by `commit`. Nothing is sent when
+    /// there are no mutations.
+    pub async fn mutate_many(&mut self, mut mutations: Vec<api::Mutation>) -> Result<api::Response, DgraphError> {
+        if mutations.is_empty() {
+            return Ok(api::Response::new());
+        }
+        for mu in mutations.iter_mut() {
+            mu.commit_now = false;
+        }
+
+        let req = api::Request {
+            mutations: mutations.into(),
+            ..Default::default()
+        };
+        self._do(req, self.client.timeout).await
+    }
+
+    /// Sends a batch of mutations, split over as few requests as its size limit allows, and
+    /// returns the uids assigned to blank nodes across all of them
+    pub async fn mutate_batch(&mut self, batch: MutationBatch) -> Result<Assigned, DgraphError> {
+        let mut assigned = Assigned::default();
+        for mutations in batch.into_requests() {
+            let res = self.mutate_many(mutations).await?;
+            assigned.uids.extend(res.uids);
+        }
+        Ok(assigned)
+    }
+
+    /// Serializes `value` to json and sets it, returning the uids assigned to any blank nodes
+    pub async fn set<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<Assigned, DgraphError> {
+        let mu = mutation::set_json(value)?;
+        self.mutate(mu).await.map(Assigned::from)
+    }
+
+    /// Serializes `value` to json and deletes it
+    pub async fn delete<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<Assigned, DgraphError> {
+        let mu = mutation::delete_json(value)?;
+        self.mutate(mu).await.map(Assigned::from)
+    }
+
+    pub async fn upsert(&mut self, q: impl Into<String>, mu: api::Mutation) -> Result<api::Response, DgraphError> {
+        let req = self.upsert_request(q.into(), mu);
+        self._do(req, self.client.timeout).await
+    }
+
+    pub async fn upsert_timeout(
+        &mut self,
+        q: impl Into<String>,
+        mu: api::Mutation,
+        timeout: Duration,
+    ) -> Result<api::Response, DgraphError> {
+        let req = self.upsert_request(q.into(), mu);
+        self._do(req, Some(timeout)).await
     }
 
+    /// Like `upsert`, but runs as part of this transaction instead of committing immediately,
+    /// so that several upserts can be committed atomically with `commit`
+    pub async fn upsert_in_txn(&mut self, q: impl Into<String>, mut mu: api::Mutation) -> Result<api::Response, DgraphError> {
+        mu.commit_now = false;
+        let req = api::Request {
+            query: q.into(),
+            mutations: vec![mu].into(),
+            ..Default::default()
+        };
+        self._do(req, self.client.timeout).await
+    }
+
+    /// Runs a query along with several mutations, each applied only if its condition holds,
+    /// and commits. The response reports which of the mutations were applied, as evaluated
+    /// on the client from counts of the condition's variables.
+    pub async fn conditional_upsert(&mut self, upsert: ConditionalUpsert) -> Result<UpsertResponse, DgraphError> {
+        self._conditional_upsert(upsert, true).await
+    }
+
+    /// Like `conditional_upsert`, but runs as part of this transaction instead of committing
+    /// immediately
+    pub async fn conditional_upsert_in_txn(&mut self, upsert: ConditionalUpsert) -> Result<UpsertResponse, DgraphError> {
+        self._conditional_upsert(upsert, false).await
+    }
+
+    async fn _conditional_upsert(&mut self, upsert: ConditionalUpsert, commit_now: bool) -> Result<UpsertResponse, DgraphError> {
+        let (mut req, conditions) = upsert.into_request()?;
+        for mu in req.mutations.iter_mut() {
+            mu.commit_now = commit_now;
+        }
+        req.commit_now = commit_now;
+
+        let res = self._do(req, self.client.timeout).await?;
+        UpsertResponse::new(res, conditions)
+    }
 
     pub async fn commit(&mut self) -> Result<(), DgraphError> {
         match (self.read_only, self.finished) {
-            (true, _) => return Err(DgraphError::ReadOnly),
-            (_, true) => return Err(DgraphError::Finished),
+            (true, _) => Err(DgraphError::ReadOnly),
+            (_, true) => Err(DgraphError::Finished),
             _ => self.commit_or_abort().await,
         }
     }
 
+    pub async fn commit_timeout(&mut self, timeout: Duration) -> Result<(), DgraphError> {
+        match (self.read_only, self.finished) {
+            (true, _) => Err(DgraphError::ReadOnly),
+            (_, true) => Err(DgraphError::Finished),
+            _ => self._commit_or_abort(Some(timeout)).await,
+        }
+    }
+
     pub async fn commit_or_abort(&mut self) -> Result<(), DgraphError> {
+        self._commit_or_abort(self.client.timeout).await
+    }
+
+    pub async fn discard(&mut self) -> Result<(), DgraphError> {
+        self.context.aborted = true;
+        self.commit_or_abort().await
+    }
+
+    fn query_request(&self, query: String, vars: HashMap<String, String>) -> api::Request {
+        api::Request {
+            query,
+            start_ts: self.context.start_ts,
+            read_only: self.read_only,
+            best_effort: self.best_effort,
+            vars,
+            ..Default::default()
+        }
+    }
+
+    fn mutate_request(&self, mu: api::Mutation) -> api::Request {
+        api::Request {
+            start_ts: self.context.start_ts,
+            commit_now: mu.commit_now,
+            mutations: vec![mu].into(),
+            ..Default::default()
+        }
+    }
+
+    fn upsert_request(&self, query: String, mut mu: api::Mutation) -> api::Request {
+        mu.commit_now = true;
+        api::Request {
+            query,
+            mutations: vec![mu].into(),
+            commit_now: true,
+            ..Default::default()
+        }
+    }
+
+    async fn _commit_or_abort(&mut self, timeout: Option<Duration>) -> Result<(), DgraphError> {
         if self.finished {
             return Ok(());
         }
@@ -153,20 +620,31 @@ impl<'a> Txn<'a> {
             return Ok(());
         }
 
-        self.dc.commit_or_abort(
-            Default::default(),
-            self.context.clone(),
-        ).join_metadata_result().compat().await?;
+        let res = self.send_commit_or_abort(timeout).await;
 
-        Ok(())
+        // The commit may or may not have been applied, so make sure the transaction is aborted
+        if let Err(DgraphError::Timeout(_)) = res {
+            if !self.context.aborted {
+                self.context.aborted = true;
+                let _ = self.send_commit_or_abort(self.client.timeout).await;
+            }
+        }
+
+        res
     }
 
-    pub async fn discard(&mut self) -> Result<(), DgraphError> {
-        self.context.aborted = true;
-        self.commit_or_abort().await
+    async fn send_commit_or_abort(&self, timeout: Option<Duration>) -> Result<(), DgraphError> {
+        let endpoint = &self.client.endpoints[self.endpoint];
+        let context = &self.context;
+        let _in_flight = endpoint.start_request();
+        self.client.call(timeout, |options| {
+            endpoint.client().commit_or_abort(options, context.clone())
+        }).await?;
+
+        Ok(())
     }
 
-    async fn _do(&mut self, mut req: api::Request) -> Result<api::Response, DgraphError> {
+    async fn _do(&mut self, mut req: api::Request, timeout: Option<Duration>) -> Result<api::Response, DgraphError> {
         if self.finished {
             return Err(DgraphError::Finished);
         }
@@ -182,13 +660,14 @@ impl<'a> Txn<'a> {
 
         let commit_now = req.commit_now;
 
-        let query_res = self.dc.query(
-            Default::default(),
-            req,
-        ).join_metadata_result().compat().await;
+        let endpoint = &self.client.endpoints[self.endpoint];
+        let in_flight = endpoint.start_request();
+        let query_res = self.client.call(timeout, |options| {
+            endpoint.client().query(options, req.clone())
+        }).await;
+        drop(in_flight);
 
-        // TODO: Handle JWT failure by logging in again
-        if let Err(_) = query_res {
+        if query_res.is_err() {
             let _ = self.discard().await;
         }
         let query_res = query_res?;
@@ -197,13 +676,13 @@ impl<'a> Txn<'a> {
             self.finished = true;
         }
 
-        let txn = match query_res.1.txn.as_ref() {
+        let txn = match query_res.txn.as_ref() {
             Some(txn) => txn,
             None => return Err(DgraphError::EmptyTransaction)
         };
 
         self.merge_context(txn)?;
-        Ok(query_res.1)
+        Ok(query_res)
     }
 
     fn merge_context(&mut self, src: &api::TxnContext) -> Result<(), DgraphError> {
@@ -227,6 +706,35 @@ impl<'a> Txn<'a> {
     }
 }
 
+impl Drop for Txn {
+    fn drop(&mut self) {
+        if self.finished || !self.mutated {
+            return;
+        }
+
+        if let Some(executor) = self.client.drop_executor.as_ref() {
+            // Only clients that opted in to discard on drop get the warning, so the crate
+            // doesn't write to stderr on its own
+            #[cfg(debug_assertions)]
+            eprintln!(
+                "dgraph-rs: Txn with start_ts {} dropped without commit or discard",
+                self.context.start_ts,
+            );
+
+            let mut context = self.context.clone();
+            context.aborted = true;
+
+            let abort = self.client.endpoints[self.endpoint].client()
+                .commit_or_abort(self.client.request_options(self.client.timeout), context)
+                .drop_metadata()
+                .compat()
+                .map(|_| ());
+
+            executor.spawn(abort.boxed());
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -241,7 +749,7 @@ mod tests {
 
         let client = api_grpc::DgraphClient::with_client(
             Arc::new(
-                Client::new_plain(addr.as_ref(), port, ClientConf {
+                Client::new_plain(addr, port, ClientConf {
                     ..Default::default()
                 }).expect("Failed to initialize client stub")
             )
@@ -250,6 +758,99 @@ mod tests {
         DgraphClient::new(vec![client])
     }
 
+    #[test]
+    fn test_check_version() {
+        async_std::task::block_on(async {
+            let dg = local_dgraph_client();
+
+            dg.ensure_supported_version()
+                .await
+                .expect("Unsupported dgraph version");
+        });
+    }
+
+    #[test]
+    fn test_txn_is_send_static() {
+        fn assert_send_static<T: Send + 'static>(_: T) {}
+
+        let dg = local_dgraph_client();
+        let mut txn = dg.new_txn();
+        assert_send_static(async move {
+            txn.query("{ q0(func: has(node_key)) { uid } }").await?;
+            txn.mutate(api::Mutation::default()).await?;
+            txn.commit().await
+        });
+        assert_send_static(dg.clone());
+    }
+
+    #[test]
+    fn test_discard_on_drop() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let spawned = Arc::new(AtomicUsize::new(0));
+        let counter = spawned.clone();
+        let dg = local_dgraph_client().discard_on_drop(move |_fut: BoxFuture<'static, ()>| {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+
+        // Unmutated and finished transactions have nothing to discard
+        drop(dg.new_txn());
+        let mut txn = dg.new_txn();
+        txn.mutated = true;
+        txn.finished = true;
+        drop(txn);
+        assert_eq!(spawned.load(Ordering::SeqCst), 0);
+
+        let mut txn = dg.new_txn();
+        txn.mutated = true;
+        drop(txn);
+        assert_eq!(spawned.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_mutate_many_empty() {
+        async_std::task::block_on(async {
+            let dg = local_dgraph_client();
+            let mut txn = dg.new_txn();
+
+            txn.mutate_many(vec![]).await.expect("Empty mutate_many should not send a request");
+            assert!(!txn.mutated);
+        });
+    }
+
+    #[test]
+    fn test_set_schema() {
+        async_std::task::block_on(async {
+            let dg = local_dgraph_client();
+
+            dg.set_schema(r#"
+                node_key: string @upsert @index(hash) .
+                process_name: string @index(exact, hash, trigram, fulltext) .
+            "#)
+                .await
+                .expect("Failed to set schema");
+        });
+    }
+
+    #[test]
+    fn test_schema() {
+        async_std::task::block_on(async {
+            let dg = local_dgraph_client();
+
+            let required: schema::Schema = "node_key: string @upsert @index(hash) ."
+                .parse()
+                .expect("Failed to parse schema");
+            dg.alter_schema(&required)
+                .await
+                .expect("Failed to set schema");
+
+            let schema = dg.schema()
+                .await
+                .expect("Failed to query schema");
+            schema.check_includes(&required).expect("Schema is missing node_key");
+        });
+    }
+
     // This is a basic smoke test - query for node_key, assert we get a response
     #[test]
     fn test_query() {
@@ -325,6 +926,65 @@ mod tests {
         });
     }
 
+    #[test]
+    fn test_run_txn() {
+        use futures::FutureExt;
+
+        async_std::task::block_on(async {
+            let dg = local_dgraph_client();
+
+            let uid = dg.run_txn(|txn| async move {
+                let assigned = txn.set(&serde_json::json!({
+                    "uid": "_:process",
+                    "node_key": "{1f1e2a4c-2c5e-4b8e-9d51-0b2bd5e0b8a1}",
+                    "process_name": "baz.exe",
+                })).await?;
+
+                Ok(assigned.uid("_:process").map(String::from))
+            }.boxed())
+                .await
+                .expect("Transaction failed");
+
+            assert!(uid.is_some());
+        });
+    }
+
+    #[test]
+    fn test_upsert_in_txn() {
+        async_std::task::block_on(async {
+            let dg = local_dgraph_client();
+
+            let mut txn = dg.new_txn();
+            for (node_key, process_name) in &[
+                ("{6c1e1f2b-2f4b-4f0e-8d53-5e4f0d2b7a10}", "parent.exe"),
+                ("{0b8f3f43-6f5e-4e2b-a2a9-2f0c1a3d9e21}", "child.exe"),
+            ] {
+                let query = format!(r#"
+                    {{
+                      p as var(func: eq(node_key, "{}"))
+                    }}
+                    "#, node_key);
+
+                let mu = mutation::set_json(&serde_json::json!({
+                    "uid": "uid(p)",
+                    "node_key": node_key,
+                    "process_name": process_name,
+                })).expect("json");
+
+                txn.upsert_in_txn(query, mu)
+                    .await
+                    .expect("Request to dgraph failed");
+            }
+
+            txn.commit().await.expect("Commit failed");
+
+            let uid = node_key_to_uid(&dg, "{0b8f3f43-6f5e-4e2b-a2a9-2f0c1a3d9e21}")
+                .await
+                .expect("nktu");
+            assert!(uid.is_some());
+        });
+    }
+
     async fn node_key_to_uid(dg: &DgraphClient, node_key: &str) -> Result<Option<String>, DgraphError> {
 
         let mut txn = dg.new_read_only();
@@ -353,6 +1013,42 @@ mod tests {
         Ok(uid)
     }
 
+    #[test]
+    fn test_query_decode_block() {
+        async_std::task::block_on(async {
+            let dg = local_dgraph_client();
+            let mut txn = dg.new_read_only();
+
+            #[derive(serde::Deserialize)]
+            struct Node {
+                uid: String,
+            }
+
+            let mut vars = HashMap::new();
+            vars.insert("$a".to_string(), "{453120d4-5c9f-43f6-b7af-28b376b3a993}".to_string());
+
+            let res = txn.query_with_vars(r"
+                query q0($a: string)
+                {
+                    q0(func: eq(node_key, $a), first: 1) {
+                        uid
+                    }
+                }
+                ", vars)
+                .await
+                .expect("query");
+
+            let nodes: Vec<Node> = res.decode_block("q0").expect("decode_block");
+            assert!(nodes.len() <= 1);
+            assert!(nodes.iter().all(|node| node.uid.starts_with("0x")));
+
+            match res.decode_block::<Node>("missing") {
+                Err(DgraphError::MissingBlock(_)) => (),
+                _ => panic!("Expected a MissingBlock error for missing"),
+            }
+        });
+    }
+
     #[test]
     fn test_txn_query_mutate() {
         async_std::task::block_on(async {
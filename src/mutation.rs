@@ -0,0 +1,220 @@
+use crate::errors::DgraphError;
+use crate::protos::api;
+
+use protobuf::Message;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// gRPC servers reject messages over 4MiB by default, so leave room for the rest of the request
+pub const DEFAULT_MAX_REQUEST_SIZE: usize = 2 * 1024 * 1024;
+
+/// Builds a mutation that sets `nquads`, such as those built with `nquad::NQuad`
+pub fn set_nquads<N: Into<api::NQuad>>(nquads: impl IntoIterator<Item=N>) -> api::Mutation {
+    api::Mutation {
+        set: nquads.into_iter().map(Into::into).collect(),
+        ..Default::default()
+    }
+}
+
+/// Builds a mutation that deletes `nquads`
+pub fn del_nquads<N: Into<api::NQuad>>(nquads: impl IntoIterator<Item=N>) -> api::Mutation {
+    api::Mutation {
+        del: nquads.into_iter().map(Into::into).collect(),
+        ..Default::default()
+    }
+}
+
+/// Builds a mutation that sets `value`, serialized as json
+pub fn set_json<T: Serialize + ?Sized>(value: &T) -> Result<api::Mutation, DgraphError> {
+    Ok(api::Mutation {
+        set_json: serde_json::to_vec(value).map_err(DgraphError::SerializeError)?,
+        ..Default::default()
+    })
+}
+
+/// Builds a mutation that deletes `value`, serialized as json
+pub fn delete_json<T: Serialize + ?Sized>(value: &T) -> Result<api::Mutation, DgraphError> {
+    Ok(api::Mutation {
+        delete_json: serde_json::to_vec(value).map_err(DgraphError::SerializeError)?,
+        ..Default::default()
+    })
+}
+
+/// The uids the server assigned to blank nodes created by a mutation
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Assigned {
+    pub uids: HashMap<String, String>,
+}
+
+impl Assigned {
+    /// Looks up the uid assigned to a blank node, given either as `_:name` or `name`
+    pub fn uid(&self, blank_node: &str) -> Option<&str> {
+        let name = blank_node.trim_start_matches("_:");
+        self.uids.get(name).map(String::as_str)
+    }
+}
+
+impl From<api::Response> for Assigned {
+    fn from(res: api::Response) -> Self {
+        Self { uids: res.uids }
+    }
+}
+
+/// Accumulates mutations to be sent together with `Txn::mutate_batch`. Batches larger than
+/// `max_request_size` are split over several requests in the same transaction.
+#[derive(Debug, Clone)]
+pub struct MutationBatch {
+    mutations: Vec<api::Mutation>,
+    max_request_size: usize,
+}
+
+impl MutationBatch {
+    pub fn new() -> Self {
+        Self {
+            mutations: Vec::new(),
+            max_request_size: DEFAULT_MAX_REQUEST_SIZE,
+        }
+    }
+
+    /// The maximum encoded size of a single request, counting the framing of each mutation
+    /// and the request's other fields. A mutation larger than this on its own is still sent,
+    /// in a request by itself.
+    pub fn max_request_size(mut self, max_request_size: usize) -> Self {
+        self.max_request_size = max_request_size;
+        self
+    }
+
+    pub fn set<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<&mut Self, DgraphError> {
+        self.mutations.push(set_json(value)?);
+        Ok(self)
+    }
+
+    pub fn delete<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<&mut Self, DgraphError> {
+        self.mutations.push(delete_json(value)?);
+        Ok(self)
+    }
+
+    pub fn set_nquads(&mut self, nquads: impl Into<Vec<u8>>) -> &mut Self {
+        self.mutations.push(api::Mutation {
+            set_nquads: nquads.into(),
+            ..Default::default()
+        });
+        self
+    }
+
+    pub fn del_nquads(&mut self, nquads: impl Into<Vec<u8>>) -> &mut Self {
+        self.mutations.push(api::Mutation {
+            del_nquads: nquads.into(),
+            ..Default::default()
+        });
+        self
+    }
+
+    pub fn mutation(&mut self, mu: api::Mutation) -> &mut Self {
+        self.mutations.push(mu);
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.mutations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mutations.is_empty()
+    }
+
+    /// Splits the batch into groups of mutations that each fit in a single request
+    pub(crate) fn into_requests(self) -> Vec<Vec<api::Mutation>> {
+        // The largest the fields other than the mutations can be once the transaction is set
+        let base_size = api::Request {
+            start_ts: u64::MAX,
+            commit_now: true,
+            ..Default::default()
+        }.compute_size() as usize;
+
+        let mut requests = Vec::new();
+        let mut current = Vec::new();
+        let mut current_size = base_size;
+
+        for mut mu in self.mutations {
+            mu.commit_now = false;
+            // Each mutation is written with a tag and a length prefix
+            let len = mu.compute_size();
+            let size = (1 + protobuf::rt::compute_raw_varint32_size(len) + len) as usize;
+
+            if !current.is_empty() && current_size + size > self.max_request_size {
+                requests.push(std::mem::take(&mut current));
+                current_size = base_size;
+            }
+
+            current_size += size;
+            current.push(mu);
+        }
+
+        if !current.is_empty() {
+            requests.push(current);
+        }
+
+        requests
+    }
+}
+
+impl Default for MutationBatch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_json() {
+        let value = serde_json::json!({
+            "uid": "_:process",
+            "process_name": "bar.exe",
+        });
+
+        let mu = set_json(&value).unwrap();
+        let round_trip: serde_json::Value = serde_json::from_slice(&mu.set_json).unwrap();
+        assert_eq!(round_trip, value);
+        assert!(mu.delete_json.is_empty());
+    }
+
+    #[test]
+    fn test_batch_split() {
+        let mut batch = MutationBatch::new().max_request_size(100);
+        for i in 0..10 {
+            batch.set_nquads(format!("_:n{} <node_key> \"{:0>20}\" .", i, i));
+        }
+        batch.set_nquads(vec![b'x'; 200]);
+        assert_eq!(batch.len(), 11);
+
+        let requests = batch.into_requests();
+        assert!(requests.len() > 2);
+        assert_eq!(requests.iter().map(Vec::len).sum::<usize>(), 11);
+        assert_eq!(requests.last().unwrap().len(), 1);
+
+        for request in &requests[..requests.len() - 1] {
+            let request = api::Request {
+                start_ts: u64::MAX,
+                mutations: request.clone().into(),
+                commit_now: true,
+                ..Default::default()
+            };
+            assert!(request.compute_size() <= 100);
+        }
+    }
+
+    #[test]
+    fn test_assigned_uid() {
+        let mut res = api::Response::new();
+        res.uids.insert("process".to_string(), "0x2a".to_string());
+
+        let assigned = Assigned::from(res);
+        assert_eq!(assigned.uid("_:process"), Some("0x2a"));
+        assert_eq!(assigned.uid("process"), Some("0x2a"));
+        assert_eq!(assigned.uid("file"), None);
+    }
+}
@@ -0,0 +1,99 @@
+//! Mapping Rust structs to Dgraph nodes, usually through `#[derive(DgraphNode)]` with the
+//! `derive` feature.
+//!
+//! ```ignore
+//! #[derive(DgraphNode)]
+//! #[dgraph(type = "Process")]
+//! struct Process {
+//!     uid: Option<Uid>,
+//!     #[dgraph(index(hash), upsert)]
+//!     node_key: String,
+//!     #[dgraph(index(exact, trigram))]
+//!     process_name: Option<String>,
+//!     #[dgraph(edge, reverse)]
+//!     bin_file: Option<File>,
+//!     #[dgraph(edge, reverse)]
+//!     children: Vec<Process>,
+//! }
+//!
+//! dg.set_node_schema::<Process>().await?;
+//! let query = format!("{{ q0(func: type(Process)) {{ {} }} }}", Process::selection());
+//! ```
+//!
+//! The derive also implements `Serialize` and `Deserialize`, writing `dgraph.type` and the
+//! uid for JSON mutations, and reading uid edges whether Dgraph returns them as an object or
+//! a list. Fields attributes are:
+//!
+//! - `uid`, for the uid field when it is not named `uid`
+//! - `predicate = "name"`, when the predicate is not named after the field
+//! - `edge`, for fields holding other nodes, as `T`, `Option<T>` or `Vec<T>`
+//! - `type = "string"`, when the scalar type can't be inferred from the field type. `u64` and
+//!   `usize` fields need `type = "int"`, since Dgraph's int is a signed 64 bit integer.
+//! - `index(tokenizers...)`, `upsert`, `reverse`, `count` and `lang` for the schema
+//! - `skip`, for fields that are not stored, which must implement `Default`
+
+/// How many levels of edges `DgraphNode::selection` follows
+pub const DEFAULT_SELECTION_DEPTH: usize = 3;
+
+pub trait DgraphNode {
+    /// The `dgraph.type` of the node
+    const TYPE: &'static str;
+
+    /// The predicates to select for this node, following edges `depth` levels deep. Edges
+    /// past that depth are left out.
+    fn selection_depth(depth: usize) -> String;
+
+    fn selection() -> String {
+        Self::selection_depth(DEFAULT_SELECTION_DEPTH)
+    }
+
+    /// The schema for the node's predicates, followed by its type definition
+    fn schema() -> String;
+}
+
+#[doc(hidden)]
+pub mod __private {
+    pub extern crate serde;
+
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::time::SystemTime;
+
+    pub struct Rfc3339<'a>(pub &'a SystemTime);
+
+    impl<'a> Serialize for Rfc3339<'a> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            crate::datetime::rfc3339::serialize(self.0, serializer)
+        }
+    }
+
+    pub struct Rfc3339List<'a>(pub &'a [SystemTime]);
+
+    impl<'a> Serialize for Rfc3339List<'a> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            crate::datetime::rfc3339::vec::serialize(self.0, serializer)
+        }
+    }
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany<T> {
+        One(T),
+        Many(Vec<T>),
+    }
+
+    /// Reads a uid edge returned either as an object or as a list of one
+    pub fn one_or_first<'de, D: Deserializer<'de>, T: Deserialize<'de>>(deserializer: D) -> Result<T, D::Error> {
+        optional_one_or_first(deserializer)?
+            .ok_or_else(|| serde::de::Error::custom("expected a node, found an empty list"))
+    }
+
+    pub fn optional_one_or_first<'de, D: Deserializer<'de>, T: Deserialize<'de>>(
+        deserializer: D,
+    ) -> Result<Option<T>, D::Error> {
+        match Option::<OneOrMany<T>>::deserialize(deserializer)? {
+            Some(OneOrMany::One(node)) => Ok(Some(node)),
+            Some(OneOrMany::Many(nodes)) => Ok(nodes.into_iter().next()),
+            None => Ok(None),
+        }
+    }
+}
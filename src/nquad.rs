@@ -0,0 +1,132 @@
+use crate::protos::api;
+use crate::value::Uid;
+
+/// Builds an `api::NQuad`, for the `set` and `del` fields of `api::Mutation`.
+///
+/// ```ignore
+/// let nquad = NQuad::blank("process")
+///     .pred("process_name")
+///     .str("bar.exe")
+///     .build();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct NQuad {
+    nquad: api::NQuad,
+}
+
+impl NQuad {
+    /// Starts an NQuad with a raw subject, such as `0x2a`, `_:process` or `uid(p)`
+    pub fn new(subject: impl Into<String>) -> Self {
+        let mut nquad = api::NQuad::new();
+        nquad.subject = subject.into();
+        Self { nquad }
+    }
+
+    /// Starts an NQuad whose subject is the blank node `_:name`
+    pub fn blank(name: &str) -> Self {
+        Self::new(blank_node(name))
+    }
+
+    pub fn uid(uid: u64) -> Self {
+        Self::new(Uid(uid).to_string())
+    }
+
+    /// Starts an NQuad whose subject is the uid of a query variable, for upserts
+    pub fn var(name: &str) -> Self {
+        Self::new(format!("uid({})", name))
+    }
+
+    pub fn pred(mut self, predicate: impl Into<String>) -> Self {
+        self.nquad.predicate = predicate.into();
+        self
+    }
+
+    pub fn value(mut self, value: impl Into<api::Value>) -> Self {
+        self.nquad.set_object_value(value.into());
+        self
+    }
+
+    pub fn str(self, value: impl Into<String>) -> Self {
+        self.value(value.into())
+    }
+
+    pub fn int(self, value: i64) -> Self {
+        self.value(value)
+    }
+
+    pub fn double(self, value: f64) -> Self {
+        self.value(value)
+    }
+
+    pub fn bool(self, value: bool) -> Self {
+        self.value(value)
+    }
+
+    /// Makes this NQuad an edge to another node, given as a raw id such as `0x2a` or `_:file`
+    pub fn object(mut self, object_id: impl Into<String>) -> Self {
+        self.nquad.object_id = object_id.into();
+        self
+    }
+
+    pub fn object_blank(self, name: &str) -> Self {
+        self.object(blank_node(name))
+    }
+
+    pub fn object_uid(self, uid: u64) -> Self {
+        self.object(Uid(uid).to_string())
+    }
+
+    pub fn lang(mut self, lang: impl Into<String>) -> Self {
+        self.nquad.lang = lang.into();
+        self
+    }
+
+    pub fn facet(mut self, facet: impl Into<api::Facet>) -> Self {
+        self.nquad.facets.push(facet.into());
+        self
+    }
+
+    pub fn build(self) -> api::NQuad {
+        self.nquad
+    }
+}
+
+impl From<NQuad> for api::NQuad {
+    fn from(nquad: NQuad) -> Self {
+        nquad.build()
+    }
+}
+
+fn blank_node(name: &str) -> String {
+    if name.starts_with("_:") {
+        name.to_string()
+    } else {
+        format!("_:{}", name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_nquad() {
+        let nquad = NQuad::blank("process")
+            .pred("process_name")
+            .str("bar.exe")
+            .lang("en")
+            .build();
+
+        assert_eq!(nquad.subject, "_:process");
+        assert_eq!(nquad.predicate, "process_name");
+        assert_eq!(nquad.get_object_value().get_str_val(), "bar.exe");
+        assert_eq!(nquad.lang, "en");
+
+        let edge = NQuad::uid(0x2a).pred("bin_file").object_blank("_:file").build();
+        assert_eq!(edge.subject, "0x2a");
+        assert_eq!(edge.object_id, "_:file");
+        assert!(!edge.has_object_value());
+
+        assert_eq!(NQuad::var("p").build().subject, "uid(p)");
+    }
+}
@@ -0,0 +1,903 @@
+//! Builds query text for `Txn::query` and `Txn::query_with_vars`.
+//!
+//! ```ignore
+//! let query = Query::new()
+//!     .block(
+//!         Block::new("q0", Func::eq("node_key", node_key))
+//!             .filter(Func::has("process_name").and(Filter::not(Func::eq("state", "exited"))))
+//!             .first(10)
+//!             .field("uid")
+//!             .field("process_name")
+//!             .edge(Edge::new("children").field("uid")),
+//!     )
+//!     .build()?;
+//! ```
+//!
+//! Values are always quoted and escaped, and names are checked, so neither can change the
+//! structure of the query.
+use crate::errors::DgraphError;
+use crate::geo::{Geometry, Point, Polygon};
+use crate::value::Uid;
+
+use std::fmt::Write;
+
+/// An argument to a query function
+#[derive(Debug, Clone, PartialEq)]
+pub enum Arg {
+    Str(String),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Uid(u64),
+    /// A GraphQL variable, rendered as `$name`
+    Var(String),
+    /// A query variable defined with `name as`, rendered as `name`
+    Ref(String),
+    List(Vec<Arg>),
+}
+
+/// A GraphQL variable, as in `Func::eq("name", var("name"))`
+pub fn var(name: &str) -> Arg {
+    Arg::Var(name.trim_start_matches('$').to_string())
+}
+
+macro_rules! arg_from {
+    ($ty:ty, $variant:ident, $conv:expr) => {
+        impl From<$ty> for Arg {
+            fn from(v: $ty) -> Self {
+                Arg::$variant($conv(v))
+            }
+        }
+    };
+}
+
+arg_from!(String, Str, |v| v);
+arg_from!(&str, Str, str::to_string);
+arg_from!(&String, Str, String::clone);
+arg_from!(i64, Int, |v| v);
+arg_from!(i32, Int, i64::from);
+arg_from!(u32, Int, i64::from);
+arg_from!(f64, Float, |v| v);
+arg_from!(f32, Float, f64::from);
+arg_from!(bool, Bool, |v| v);
+arg_from!(Uid, Uid, |v: Uid| v.0);
+
+impl<T: Into<Arg>> From<Vec<T>> for Arg {
+    fn from(v: Vec<T>) -> Self {
+        Arg::List(v.into_iter().map(Into::into).collect())
+    }
+}
+
+impl Arg {
+    fn render(&self, out: &mut String) -> Result<(), DgraphError> {
+        match self {
+            Arg::Str(s) => out.push_str(&quote(s)),
+            Arg::Int(i) => write!(out, "{}", i).unwrap(),
+            Arg::Float(f) if !f.is_finite() => {
+                return Err(DgraphError::InvalidArgument(format!("Non finite float {}", f)));
+            }
+            Arg::Float(f) if f.fract() == 0.0 => write!(out, "{:.1}", f).unwrap(),
+            Arg::Float(f) => write!(out, "{}", f).unwrap(),
+            Arg::Bool(b) => write!(out, "{}", b).unwrap(),
+            Arg::Uid(uid) => write!(out, "{:#x}", uid).unwrap(),
+            Arg::Var(name) => write!(out, "${}", name_ref(name)?).unwrap(),
+            Arg::Ref(name) => out.push_str(name_ref(name)?),
+            Arg::List(items) => {
+                out.push('[');
+                render_list(out, items, Arg::render)?;
+                out.push(']');
+            }
+        }
+        Ok(())
+    }
+}
+
+fn quote(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn render_list<T>(
+    out: &mut String,
+    items: &[T],
+    render: impl Fn(&T, &mut String) -> Result<(), DgraphError>,
+) -> Result<(), DgraphError> {
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            out.push_str(", ");
+        }
+        render(item, out)?;
+    }
+    Ok(())
+}
+
+/// Escapes the `/` that would end a regex literal. Existing `\x` escapes are kept as they are,
+/// so a trailing lone `\` is rejected rather than escaping the closing `/`.
+fn regex_pattern(pattern: &str) -> Result<String, DgraphError> {
+    let mut escaped = String::with_capacity(pattern.len());
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(next) => {
+                    escaped.push('\\');
+                    escaped.push(next);
+                }
+                None => return Err(DgraphError::InvalidArgument(format!("Regex ends in a lone \\: {:?}", pattern))),
+            },
+            '/' => escaped.push_str("\\/"),
+            c => escaped.push(c),
+        }
+    }
+    Ok(escaped)
+}
+
+/// Block names, aliases and variables
+pub(crate) fn name_ref(name: &str) -> Result<&str, DgraphError> {
+    let mut chars = name.chars();
+    let valid = chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+    if valid {
+        Ok(name)
+    } else {
+        Err(DgraphError::InvalidName(name.to_string()))
+    }
+}
+
+/// Predicates are written as is when possible, and as `<predicate>` otherwise.
+/// `count(predicate)` and `val(var)` are accepted where a predicate is.
+fn predicate(predicate: &str) -> Result<String, DgraphError> {
+    for function in &["count", "val"] {
+        let inner = predicate.strip_prefix(function)
+            .and_then(|p| p.strip_prefix('('))
+            .and_then(|p| p.strip_suffix(')'));
+        if let Some(inner) = inner {
+            let inner = if *function == "val" { name_ref(inner)?.to_string() } else { self::predicate(inner)? };
+            return Ok(format!("{}({})", function, inner));
+        }
+    }
+
+    let plain = |c: char| c.is_alphanumeric() || c == '_' || c == '.';
+    let forbidden = |c: char| "<>\"{}|\\^`".contains(c) || c.is_whitespace() || c.is_control();
+
+    if predicate.is_empty() || predicate.contains(forbidden) {
+        Err(DgraphError::InvalidName(predicate.to_string()))
+    } else if predicate.chars().all(plain) {
+        Ok(predicate.to_string())
+    } else {
+        Ok(format!("<{}>", predicate))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum FuncKind {
+    Call {
+        name: &'static str,
+        predicate: Option<String>,
+        args: Vec<Arg>,
+    },
+    Regexp {
+        predicate: String,
+        pattern: String,
+        flags: String,
+    },
+    Type(String),
+    Raw(String),
+}
+
+/// A root or filter function
+#[derive(Debug, Clone, PartialEq)]
+pub struct Func(FuncKind);
+
+macro_rules! predicate_func {
+    ($($(#[$doc:meta])* $name:ident => $dgraph:expr),* $(,)?) => {
+        $(
+            $(#[$doc])*
+            pub fn $name(predicate: &str, value: impl Into<Arg>) -> Self {
+                Self::call($dgraph, Some(predicate), vec![value.into()])
+            }
+        )*
+    };
+}
+
+impl Func {
+    fn call(name: &'static str, predicate: Option<&str>, args: Vec<Arg>) -> Self {
+        Func(FuncKind::Call { name, predicate: predicate.map(str::to_string), args })
+    }
+
+    predicate_func! {
+        /// Equality, where a list value matches any of its items
+        eq => "eq",
+        le => "le",
+        lt => "lt",
+        ge => "ge",
+        gt => "gt",
+        anyofterms => "anyofterms",
+        allofterms => "allofterms",
+        anyoftext => "anyoftext",
+        alloftext => "alloftext",
+        /// Nodes whose `predicate` edge points at the given uids
+        uid_in => "uid_in",
+    }
+
+    pub fn has(predicate: &str) -> Self {
+        Self::call("has", Some(predicate), Vec::new())
+    }
+
+    /// The given uids, which may be a `Uid`, a list of them or a variable
+    pub fn uid(uids: impl Into<Arg>) -> Self {
+        let args = match uids.into() {
+            Arg::List(uids) => uids,
+            uid => vec![uid],
+        };
+        Self::call("uid", None, args)
+    }
+
+    /// The uids stored in a query variable, `uid(var)`
+    pub fn uid_var(var: &str) -> Self {
+        Self::uid(Arg::Ref(var.to_string()))
+    }
+
+    /// `regexp(predicate, /pattern/flags)`
+    pub fn regexp(predicate: &str, pattern: &str, flags: &str) -> Self {
+        Func(FuncKind::Regexp {
+            predicate: predicate.to_string(),
+            pattern: pattern.to_string(),
+            flags: flags.to_string(),
+        })
+    }
+
+    /// Nodes of the given `dgraph.type`
+    pub fn type_(name: &str) -> Self {
+        Func(FuncKind::Type(name.to_string()))
+    }
+
+    /// `near(predicate, [lon, lat], distance)`, matching nodes within `meters` of `point`
+    pub fn near(predicate: &str, point: &Point, meters: f64) -> Self {
+        Self::call("near", Some(predicate), vec![point.coordinates(), Arg::Float(meters)])
+    }
+
+    /// `within(predicate, polygon)`, matching nodes whose geometry lies inside `polygon`
+    pub fn within(predicate: &str, polygon: &Polygon) -> Self {
+        Self::call("within", Some(predicate), vec![polygon.coordinates()])
+    }
+
+    /// `contains(predicate, geometry)`, matching polygons containing `geometry`, which Dgraph
+    /// requires to be a `Point` or a `Polygon`
+    pub fn contains(predicate: &str, geometry: &impl Geometry) -> Self {
+        Self::call("contains", Some(predicate), vec![geometry.coordinates()])
+    }
+
+    /// `intersects(predicate, geometry)`, matching polygons intersecting `geometry`, which
+    /// Dgraph requires to be a `Polygon` or a `MultiPolygon`
+    pub fn intersects(predicate: &str, geometry: &impl Geometry) -> Self {
+        Self::call("intersects", Some(predicate), vec![geometry.coordinates()])
+    }
+
+    /// A function written by hand. It is not checked.
+    pub fn raw(func: impl Into<String>) -> Self {
+        Func(FuncKind::Raw(func.into()))
+    }
+
+    /// Renders the function on its own, checking its names and values as `Query::build` does
+    pub fn build(&self) -> Result<String, DgraphError> {
+        let mut out = String::new();
+        self.render(&mut out)?;
+        Ok(out)
+    }
+
+    pub fn and(self, other: impl Into<Filter>) -> Filter {
+        Filter::from(self).and(other)
+    }
+
+    pub fn or(self, other: impl Into<Filter>) -> Filter {
+        Filter::from(self).or(other)
+    }
+
+    fn render(&self, out: &mut String) -> Result<(), DgraphError> {
+        match &self.0 {
+            FuncKind::Call { name, predicate: pred, args } => {
+                write!(out, "{}(", name).unwrap();
+                if let Some(pred) = pred {
+                    out.push_str(&predicate(pred)?);
+                    if !args.is_empty() {
+                        out.push_str(", ");
+                    }
+                }
+                render_list(out, args, Arg::render)?;
+                out.push(')');
+            }
+            FuncKind::Regexp { predicate: pred, pattern, flags } => {
+                if flags.contains(|c: char| !c.is_ascii_alphabetic()) {
+                    return Err(DgraphError::InvalidName(flags.clone()));
+                }
+                write!(out, "regexp({}, /{}/{})", predicate(pred)?, regex_pattern(pattern)?, flags).unwrap();
+            }
+            FuncKind::Type(name) => write!(out, "type({})", name_ref(name)?).unwrap(),
+            FuncKind::Raw(func) => out.push_str(func),
+        }
+        Ok(())
+    }
+}
+
+/// An `@filter` expression
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    Func(Func),
+    And(Vec<Filter>),
+    Or(Vec<Filter>),
+    Not(Box<Filter>),
+}
+
+impl From<Func> for Filter {
+    fn from(func: Func) -> Self {
+        Filter::Func(func)
+    }
+}
+
+impl Filter {
+    pub fn and(self, other: impl Into<Filter>) -> Filter {
+        match self {
+            Filter::And(mut filters) => {
+                filters.push(other.into());
+                Filter::And(filters)
+            }
+            filter => Filter::And(vec![filter, other.into()]),
+        }
+    }
+
+    pub fn or(self, other: impl Into<Filter>) -> Filter {
+        match self {
+            Filter::Or(mut filters) => {
+                filters.push(other.into());
+                Filter::Or(filters)
+            }
+            filter => Filter::Or(vec![filter, other.into()]),
+        }
+    }
+
+    pub fn not(filter: impl Into<Filter>) -> Filter {
+        Filter::Not(Box::new(filter.into()))
+    }
+
+    fn render(&self, out: &mut String) -> Result<(), DgraphError> {
+        // Nested expressions are always parenthesized rather than relying on precedence
+        let render_operand = |filter: &Filter, out: &mut String| match filter {
+            Filter::And(_) | Filter::Or(_) => {
+                out.push('(');
+                filter.render(out)?;
+                out.push(')');
+                Ok(())
+            }
+            filter => filter.render(out),
+        };
+
+        match self {
+            Filter::Func(func) => func.render(out)?,
+            Filter::And(filters) | Filter::Or(filters) => {
+                let op = if let Filter::And(_) = self { " AND " } else { " OR " };
+                for (i, filter) in filters.iter().enumerate() {
+                    if i > 0 {
+                        out.push_str(op);
+                    }
+                    render_operand(filter, out)?;
+                }
+            }
+            Filter::Not(filter) => {
+                out.push_str("NOT ");
+                render_operand(filter, out)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Order {
+    Asc(String),
+    Desc(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Field {
+    Predicate {
+        predicate: String,
+        alias: Option<String>,
+        var: Option<String>,
+        facets: Option<Vec<String>>,
+    },
+    Edge(Edge),
+    Raw(String),
+}
+
+/// What root blocks and edges have in common
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Selection {
+    filter: Option<Filter>,
+    first: Option<i64>,
+    offset: Option<u64>,
+    after: Option<Uid>,
+    order: Vec<Order>,
+    cascade: bool,
+    normalize: bool,
+    fields: Vec<Field>,
+}
+
+impl Selection {
+    fn field(&mut self, predicate: &str, alias: Option<&str>, var: Option<&str>, facets: Option<&[&str]>) {
+        self.fields.push(Field::Predicate {
+            predicate: predicate.to_string(),
+            alias: alias.map(str::to_string),
+            var: var.map(str::to_string),
+            facets: facets.map(|facets| facets.iter().map(|f| f.to_string()).collect()),
+        });
+    }
+
+    fn args(&self) -> Result<Vec<String>, DgraphError> {
+        let mut args = Vec::new();
+        for order in &self.order {
+            args.push(match order {
+                Order::Asc(pred) => format!("orderasc: {}", predicate(pred)?),
+                Order::Desc(pred) => format!("orderdesc: {}", predicate(pred)?),
+            });
+        }
+        if let Some(first) = self.first {
+            args.push(format!("first: {}", first));
+        }
+        if let Some(offset) = self.offset {
+            args.push(format!("offset: {}", offset));
+        }
+        if let Some(after) = self.after {
+            args.push(format!("after: {}", after));
+        }
+        Ok(args)
+    }
+
+    fn render_directives(&self, out: &mut String) -> Result<(), DgraphError> {
+        if let Some(filter) = &self.filter {
+            out.push_str(" @filter(");
+            filter.render(out)?;
+            out.push(')');
+        }
+        if self.cascade {
+            out.push_str(" @cascade");
+        }
+        if self.normalize {
+            out.push_str(" @normalize");
+        }
+        Ok(())
+    }
+
+    fn render_fields(&self, out: &mut String, indent: usize) -> Result<(), DgraphError> {
+        if self.fields.is_empty() {
+            return Ok(());
+        }
+
+        out.push_str(" {\n");
+        for field in &self.fields {
+            out.push_str(&"  ".repeat(indent + 1));
+            match field {
+                Field::Predicate { predicate: pred, alias, var, facets } => {
+                    if let Some(alias) = alias {
+                        write!(out, "{}: ", name_ref(alias)?).unwrap();
+                    }
+                    if let Some(var) = var {
+                        write!(out, "{} as ", name_ref(var)?).unwrap();
+                    }
+                    out.push_str(&predicate(pred)?);
+                    render_facets(out, facets)?;
+                }
+                Field::Edge(edge) => edge.render(out, indent + 1)?,
+                Field::Raw(raw) => out.push_str(raw),
+            }
+            out.push('\n');
+        }
+        out.push_str(&"  ".repeat(indent));
+        out.push('}');
+        Ok(())
+    }
+}
+
+fn render_facets(out: &mut String, facets: &Option<Vec<String>>) -> Result<(), DgraphError> {
+    match facets {
+        None => (),
+        Some(facets) if facets.is_empty() => out.push_str(" @facets"),
+        Some(facets) => {
+            out.push_str(" @facets(");
+            render_list(out, facets, |facet, out| {
+                out.push_str(name_ref(facet)?);
+                Ok(())
+            })?;
+            out.push(')');
+        }
+    }
+    Ok(())
+}
+
+macro_rules! selection_methods {
+    () => {
+        pub fn filter(mut self, filter: impl Into<Filter>) -> Self {
+            self.selection.filter = Some(filter.into());
+            self
+        }
+
+        /// Negative values take from the end
+        pub fn first(mut self, first: i64) -> Self {
+            self.selection.first = Some(first);
+            self
+        }
+
+        pub fn offset(mut self, offset: u64) -> Self {
+            self.selection.offset = Some(offset);
+            self
+        }
+
+        /// Skips results up to and including `uid`, for paging by uid
+        pub fn after(mut self, uid: Uid) -> Self {
+            self.selection.after = Some(uid);
+            self
+        }
+
+        /// Orders by `predicate`, after any orderings added before it
+        pub fn order_asc(mut self, predicate: &str) -> Self {
+            self.selection.order.push(Order::Asc(predicate.to_string()));
+            self
+        }
+
+        pub fn order_desc(mut self, predicate: &str) -> Self {
+            self.selection.order.push(Order::Desc(predicate.to_string()));
+            self
+        }
+
+        pub fn cascade(mut self) -> Self {
+            self.selection.cascade = true;
+            self
+        }
+
+        pub fn normalize(mut self) -> Self {
+            self.selection.normalize = true;
+            self
+        }
+
+        pub fn field(mut self, predicate: &str) -> Self {
+            self.selection.field(predicate, None, None, None);
+            self
+        }
+
+        pub fn fields<'a>(mut self, predicates: impl IntoIterator<Item=&'a str>) -> Self {
+            for predicate in predicates {
+                self.selection.field(predicate, None, None, None);
+            }
+            self
+        }
+
+        /// `alias: predicate`
+        pub fn field_as(mut self, alias: &str, predicate: &str) -> Self {
+            self.selection.field(predicate, Some(alias), None, None);
+            self
+        }
+
+        /// `var as predicate`, storing the values in a value variable
+        pub fn field_var(mut self, var: &str, predicate: &str) -> Self {
+            self.selection.field(predicate, None, Some(var), None);
+            self
+        }
+
+        /// `predicate @facets(...)`, or every facet when `facets` is empty
+        pub fn field_facets(mut self, predicate: &str, facets: &[&str]) -> Self {
+            self.selection.field(predicate, None, None, Some(facets));
+            self
+        }
+
+        /// `count(predicate)`
+        pub fn count(mut self, predicate: &str) -> Self {
+            self.selection.field(&format!("count({})", predicate), None, None, None);
+            self
+        }
+
+        pub fn edge(mut self, edge: Edge) -> Self {
+            self.selection.fields.push(Field::Edge(edge));
+            self
+        }
+
+        /// A field written by hand, such as `expand(_all_)`. It is not checked.
+        pub fn raw(mut self, field: impl Into<String>) -> Self {
+            self.selection.fields.push(Field::Raw(field.into()));
+            self
+        }
+    };
+}
+
+/// A nested edge selection
+#[derive(Debug, Clone, PartialEq)]
+pub struct Edge {
+    predicate: String,
+    reverse: bool,
+    alias: Option<String>,
+    var: Option<String>,
+    facets: Option<Vec<String>>,
+    selection: Selection,
+}
+
+impl Edge {
+    pub fn new(predicate: &str) -> Self {
+        Self {
+            predicate: predicate.to_string(),
+            reverse: false,
+            alias: None,
+            var: None,
+            facets: None,
+            selection: Selection::default(),
+        }
+    }
+
+    /// Follows the edge backwards, `~predicate`, which needs `@reverse` in the schema
+    pub fn reverse(mut self) -> Self {
+        self.reverse = true;
+        self
+    }
+
+    pub fn alias(mut self, alias: &str) -> Self {
+        self.alias = Some(alias.to_string());
+        self
+    }
+
+    /// `var as predicate`, storing the uids reached in a variable
+    pub fn var(mut self, var: &str) -> Self {
+        self.var = Some(var.to_string());
+        self
+    }
+
+    /// `@facets(...)` on the edge, or every facet when `facets` is empty
+    pub fn facets(mut self, facets: &[&str]) -> Self {
+        self.facets = Some(facets.iter().map(|f| f.to_string()).collect());
+        self
+    }
+
+    selection_methods!();
+
+    fn render(&self, out: &mut String, indent: usize) -> Result<(), DgraphError> {
+        if let Some(alias) = &self.alias {
+            write!(out, "{}: ", name_ref(alias)?).unwrap();
+        }
+        if let Some(var) = &self.var {
+            write!(out, "{} as ", name_ref(var)?).unwrap();
+        }
+        if self.reverse {
+            out.push('~');
+        }
+        out.push_str(&predicate(&self.predicate)?);
+
+        let args = self.selection.args()?;
+        if !args.is_empty() {
+            write!(out, "({})", args.join(", ")).unwrap();
+        }
+
+        render_facets(out, &self.facets)?;
+        self.selection.render_directives(out)?;
+        self.selection.render_fields(out, indent)
+    }
+}
+
+/// A root query block, either named or a `var` block
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    name: Option<String>,
+    var: Option<String>,
+    func: Func,
+    selection: Selection,
+}
+
+impl Block {
+    pub fn new(name: &str, func: Func) -> Self {
+        Self {
+            name: Some(name.to_string()),
+            var: None,
+            func,
+            selection: Selection::default(),
+        }
+    }
+
+    /// A `var(func: ...)` block, which only defines variables and is left out of the response
+    pub fn var_block(func: Func) -> Self {
+        Self {
+            name: None,
+            var: None,
+            func,
+            selection: Selection::default(),
+        }
+    }
+
+    /// `var as name(func: ...)`, storing the uids matched in a variable
+    pub fn var(mut self, var: &str) -> Self {
+        self.var = Some(var.to_string());
+        self
+    }
+
+    selection_methods!();
+
+    fn render(&self, out: &mut String, indent: usize) -> Result<(), DgraphError> {
+        if let Some(var) = &self.var {
+            write!(out, "{} as ", name_ref(var)?).unwrap();
+        }
+        match &self.name {
+            Some(name) => out.push_str(name_ref(name)?),
+            None => out.push_str("var"),
+        }
+
+        out.push_str("(func: ");
+        self.func.render(out)?;
+        for arg in self.selection.args()? {
+            write!(out, ", {}", arg).unwrap();
+        }
+        out.push(')');
+
+        self.selection.render_directives(out)?;
+        self.selection.render_fields(out, indent)
+    }
+}
+
+/// A query made of one or more blocks
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Query {
+    name: Option<String>,
+    blocks: Vec<Block>,
+}
+
+impl Query {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Names the query, as in `query name { ... }`
+    pub fn named(name: &str) -> Self {
+        Self {
+            name: Some(name.to_string()),
+            blocks: Vec::new(),
+        }
+    }
+
+    pub fn block(mut self, block: Block) -> Self {
+        self.blocks.push(block);
+        self
+    }
+
+    /// Renders the query text, failing on invalid predicates, names or arguments
+    pub fn build(&self) -> Result<String, DgraphError> {
+        let mut out = String::new();
+        if let Some(name) = &self.name {
+            write!(out, "query {} ", name_ref(name)?).unwrap();
+        }
+
+        out.push_str("{\n");
+        for block in &self.blocks {
+            out.push_str("  ");
+            block.render(&mut out, 1)?;
+            out.push('\n');
+        }
+        out.push('}');
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build() {
+        let query = Query::new()
+            .block(
+                Block::var_block(Func::has("process_name"))
+                    .field_var("n", "process_name"),
+            )
+            .block(
+                Block::new("q0", Func::eq("node_key", "{453120d4}"))
+                    .filter(Func::has("process_name").and(Filter::not(Func::eq("state", vec!["a", "b"]))))
+                    .order_desc("created")
+                    .first(10)
+                    .offset(5)
+                    .cascade()
+                    .field("uid")
+                    .field_as("name", "process_name")
+                    .field_facets("process_name", &["origin"])
+                    .count("children")
+                    .edge(
+                        Edge::new("children")
+                            .alias("kids")
+                            .facets(&[])
+                            .first(2)
+                            .field("uid")
+                            .edge(Edge::new("bin_file").reverse().var("f").field("uid")),
+                    ),
+            )
+            .build()
+            .unwrap();
+
+        assert_eq!(query, r#"{
+  var(func: has(process_name)) {
+    n as process_name
+  }
+  q0(func: eq(node_key, "{453120d4}"), orderdesc: created, first: 10, offset: 5) @filter(has(process_name) AND NOT eq(state, ["a", "b"])) @cascade {
+    uid
+    name: process_name
+    process_name @facets(origin)
+    count(children)
+    kids: children(first: 2) @facets {
+      uid
+      f as ~bin_file {
+        uid
+      }
+    }
+  }
+}"#);
+    }
+
+    #[test]
+    fn test_funcs() {
+        let render = |filter: Filter| {
+            let mut out = String::new();
+            filter.render(&mut out).map(|_| out)
+        };
+
+        assert_eq!(render(Func::uid(vec![Uid(1), Uid(0x2a)]).into()).unwrap(), "uid(0x1, 0x2a)");
+        assert_eq!(render(Func::uid_var("v").into()).unwrap(), "uid(v)");
+        assert_eq!(render(Func::ge("count(friend)", 3).into()).unwrap(), "ge(count(friend), 3)");
+        assert_eq!(render(Func::eq("name", var("$name")).into()).unwrap(), "eq(name, $name)");
+        assert_eq!(render(Func::regexp("name", "^a/b", "i").into()).unwrap(), "regexp(name, /^a\\/b/i)");
+        assert_eq!(render(Func::type_("Process").into()).unwrap(), "type(Process)");
+        assert_eq!(render(Func::eq("my-pred", 1.0).into()).unwrap(), "eq(<my-pred>, 1.0)");
+        assert_eq!(
+            render(Func::has("a").or(Func::has("b")).and(Func::has("c"))).unwrap(),
+            "(has(a) OR has(b)) AND has(c)",
+        );
+    }
+
+    #[test]
+    fn test_injection() {
+        let query = Query::new()
+            .block(Block::new("q", Func::eq("name", "\") { uid } q1(func: has(a")).field("uid"))
+            .build()
+            .unwrap();
+        assert!(query.contains(r#"eq(name, "\") { uid } q1(func: has(a")"#));
+
+        let invalid = |block: Block| match Query::new().block(block).build() {
+            Err(DgraphError::InvalidName(_)) => (),
+            res => panic!("Expected an InvalidName error, got {:?}", res),
+        };
+
+        invalid(Block::new("q", Func::has("name) { uid }")).field("uid"));
+        invalid(Block::new("q } {", Func::has("name")));
+        invalid(Block::new("q", Func::has("name")).field("uid>"));
+        invalid(Block::new("q", Func::has("name")).field_var("1x", "uid"));
+        invalid(Block::new("q", Func::type_("Process)")));
+
+        let render = |func: Func| {
+            let mut out = String::new();
+            func.render(&mut out).map(|_| out)
+        };
+        assert_eq!(render(Func::regexp("name", r"a\/b", "")).unwrap(), r"regexp(name, /a\/b/)");
+        assert_eq!(render(Func::regexp("name", "a/) { uid } }", "")).unwrap(), r"regexp(name, /a\/) { uid } }/)");
+        match render(Func::regexp("name", r"a\", "")) {
+            Err(DgraphError::InvalidArgument(_)) => (),
+            res => panic!("Expected an InvalidArgument error, got {:?}", res),
+        }
+        match render(Func::eq("score", f64::NAN)) {
+            Err(DgraphError::InvalidArgument(_)) => (),
+            res => panic!("Expected an InvalidArgument error, got {:?}", res),
+        }
+    }
+}
@@ -0,0 +1,577 @@
+//! Parses RDF N-Quad text into `api::NQuad`s and serializes them back, as in
+//!
+//! ```text
+//! _:alice <name> "Alice"@en .
+//! _:alice <age> "29"^^<xs:int> .
+//! _:alice <friend> <0x2a> (since=2006-01-02T15:04:05, close=true) .
+//! uid(v) <visited> "true"^^<xs:boolean> .
+//! ```
+use crate::datetime;
+use crate::errors::DgraphError;
+use crate::facet::FacetValue;
+use crate::protos::api;
+use crate::protos::api::Value_oneof_val as Val;
+
+/// The predicate or object Dgraph uses for `*` in deletions
+const STAR_ALL: &str = "_STAR_ALL";
+
+/// Parses RDF text, one N-Quad per line. Empty lines and `#` comments are skipped.
+pub fn parse(rdf: &str) -> Result<Vec<api::NQuad>, DgraphError> {
+    let mut nquads = Vec::new();
+    for (i, line) in rdf.lines().enumerate() {
+        if let Some(nquad) = Parser::new(line, i + 1).nquad()? {
+            nquads.push(nquad);
+        }
+    }
+    Ok(nquads)
+}
+
+/// Serializes N-Quads to RDF text, one per line
+pub fn to_string(nquads: &[api::NQuad]) -> Result<String, DgraphError> {
+    let mut rdf = String::new();
+    for nquad in nquads {
+        rdf.push_str(&nquad_to_string(nquad)?);
+        rdf.push('\n');
+    }
+    Ok(rdf)
+}
+
+/// Serializes a single N-Quad, without a trailing newline
+pub fn nquad_to_string(nquad: &api::NQuad) -> Result<String, DgraphError> {
+    let invalid = |message: &str| DgraphError::InvalidNQuad(format!("{}: {:?}", message, nquad));
+
+    let mut rdf = node(&nquad.subject).ok_or_else(|| invalid("Invalid subject"))?;
+    rdf.push(' ');
+
+    if nquad.predicate == STAR_ALL {
+        rdf.push('*');
+    } else {
+        rdf.push_str(&iri(&nquad.predicate).ok_or_else(|| invalid("Invalid predicate"))?);
+    }
+    rdf.push(' ');
+
+    if !nquad.object_id.is_empty() {
+        rdf.push_str(&node(&nquad.object_id).ok_or_else(|| invalid("Invalid object"))?);
+    } else {
+        let val = nquad.get_object_value().val.as_ref().ok_or_else(|| invalid("Missing object"))?;
+        rdf.push_str(&literal(val, &nquad.lang).ok_or_else(|| invalid("Unsupported object value"))?);
+    }
+
+    if !nquad.label.is_empty() {
+        rdf.push(' ');
+        rdf.push_str(&iri(&nquad.label).ok_or_else(|| invalid("Invalid label"))?);
+    }
+
+    if !nquad.facets.is_empty() {
+        let facets = nquad.facets.iter()
+            .map(|facet| {
+                let value = facet_value(facet).ok_or_else(|| invalid("Invalid facet"))?;
+                Ok(format!("{}={}", facet.key, value))
+            })
+            .collect::<Result<Vec<_>, DgraphError>>()?;
+
+        rdf.push_str(" (");
+        rdf.push_str(&facets.join(", "));
+        rdf.push(')');
+    }
+
+    rdf.push_str(" .");
+    Ok(rdf)
+}
+
+fn iri(iri: &str) -> Option<String> {
+    if iri.is_empty() || iri.contains(|c: char| c == '<' || c == '>' || c.is_whitespace()) {
+        return None;
+    }
+    Some(format!("<{}>", iri))
+}
+
+fn node(node: &str) -> Option<String> {
+    if node.starts_with("_:") || node.starts_with("uid(") || node.starts_with("val(") {
+        Some(node.to_string())
+    } else {
+        iri(node)
+    }
+}
+
+fn quote(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04X}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn typed(value: impl ToString, xs_type: &str) -> String {
+    format!("{}^^<{}>", quote(&value.to_string()), xs_type)
+}
+
+fn literal(val: &Val, lang: &str) -> Option<String> {
+    let literal = match val {
+        Val::default_val(s) if s == STAR_ALL => "*".to_string(),
+        Val::default_val(s) | Val::str_val(s) if !lang.is_empty() => format!("{}@{}", quote(s), lang),
+        Val::default_val(s) => quote(s),
+        Val::str_val(s) => typed(s, "xs:string"),
+        Val::int_val(i) => typed(i, "xs:int"),
+        Val::double_val(f) if f.is_finite() => typed(f, "xs:float"),
+        Val::bool_val(b) => typed(b, "xs:boolean"),
+        Val::datetime_val(enc) | Val::date_val(enc) => {
+            let (secs, nanos, offset) = datetime::decode_binary(enc)?;
+            typed(datetime::format_rfc3339(secs, nanos, offset), "xs:dateTime")
+        }
+        Val::password_val(s) => typed(s, "xs:password"),
+        Val::uid_val(uid) => format!("<{:#x}>", uid),
+        _ => return None,
+    };
+    Some(literal)
+}
+
+fn facet_value(facet: &api::Facet) -> Option<String> {
+    // Decoded directly rather than through `FacetValue`, which drops the offset from UTC
+    if facet.val_type == api::Facet_ValType::DATETIME {
+        let (secs, nanos, offset) = datetime::decode_binary(&facet.value)?;
+        return Some(datetime::format_rfc3339(secs, nanos, offset));
+    }
+
+    let value = match FacetValue::decode(facet.val_type, &facet.value).ok()? {
+        FacetValue::String(s) => quote(&s),
+        FacetValue::Int(i) => i.to_string(),
+        // Keep a decimal point so the value is read back as a float
+        FacetValue::Float(f) if f.fract() == 0.0 => format!("{:.1}", f),
+        FacetValue::Float(f) => f.to_string(),
+        FacetValue::Bool(b) => b.to_string(),
+        FacetValue::DateTime(t) => {
+            let (secs, nanos) = datetime::from_system_time(t);
+            datetime::format_rfc3339(secs, nanos, None)
+        }
+    };
+    Some(value)
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+}
+
+impl Parser {
+    fn new(line: &str, line_number: usize) -> Self {
+        Self {
+            chars: line.chars().collect(),
+            pos: 0,
+            line: line_number,
+        }
+    }
+
+    fn error_at(&self, pos: usize, message: impl Into<String>) -> DgraphError {
+        DgraphError::InvalidRdf {
+            line: self.line,
+            column: pos + 1,
+            message: message.into(),
+        }
+    }
+
+    fn error(&self, message: impl Into<String>) -> DgraphError {
+        self.error_at(self.pos, message)
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn starts_with(&self, prefix: &str) -> bool {
+        prefix.chars().enumerate().all(|(i, c)| self.chars.get(self.pos + i) == Some(&c))
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), DgraphError> {
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.pos += 1;
+                Ok(())
+            }
+            Some(c) => Err(self.error(format!("Expected '{}', found '{}'", expected, c))),
+            None => Err(self.error(format!("Expected '{}', found end of line", expected))),
+        }
+    }
+
+    fn take_while(&mut self, f: impl Fn(char) -> bool) -> String {
+        let start = self.pos;
+        while self.peek().is_some_and(&f) {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos].iter().collect()
+    }
+
+    /// Returns `None` for empty and comment lines
+    fn nquad(&mut self) -> Result<Option<api::NQuad>, DgraphError> {
+        self.skip_whitespace();
+        match self.peek() {
+            None | Some('#') => return Ok(None),
+            _ => (),
+        }
+
+        let mut nquad = api::NQuad::new();
+
+        nquad.subject = self.node("subject")?;
+        self.skip_whitespace();
+
+        nquad.predicate = if self.peek() == Some('*') {
+            self.pos += 1;
+            STAR_ALL.to_string()
+        } else {
+            self.iri("predicate")?
+        };
+        self.skip_whitespace();
+
+        match self.peek() {
+            Some('"') => self.literal(&mut nquad)?,
+            Some('*') => {
+                self.pos += 1;
+                let mut value = api::Value::new();
+                value.set_default_val(STAR_ALL.to_string());
+                nquad.set_object_value(value);
+            }
+            _ => nquad.object_id = self.node("object")?,
+        }
+        self.skip_whitespace();
+
+        if self.peek() == Some('<') {
+            nquad.label = self.iri("label")?;
+            self.skip_whitespace();
+        }
+
+        if self.peek() == Some('(') {
+            nquad.facets = self.facets()?.into();
+            self.skip_whitespace();
+        }
+
+        self.expect('.')?;
+        self.skip_whitespace();
+        match self.peek() {
+            None | Some('#') => Ok(Some(nquad)),
+            Some(c) => Err(self.error(format!("Unexpected '{}' after end of N-Quad", c))),
+        }
+    }
+
+    fn iri(&mut self, what: &str) -> Result<String, DgraphError> {
+        if self.peek() != Some('<') {
+            return Err(self.error(format!("Expected {} IRI", what)));
+        }
+
+        let start = self.pos;
+        self.pos += 1;
+        let iri = self.take_while(|c| c != '>' && c != '<' && !c.is_whitespace());
+        if self.peek() != Some('>') {
+            return Err(self.error_at(start, format!("Unterminated {} IRI", what)));
+        }
+        self.pos += 1;
+
+        if iri.is_empty() {
+            return Err(self.error_at(start, format!("Empty {} IRI", what)));
+        }
+        Ok(iri)
+    }
+
+    /// An IRI, blank node, `uid(var)` or `val(var)`
+    fn node(&mut self, what: &str) -> Result<String, DgraphError> {
+        let start = self.pos;
+
+        if self.starts_with("_:") {
+            self.pos += 2;
+            let mut label = self.take_while(|c| !c.is_whitespace() && !"<>\"(),".contains(c));
+            // A label can't end with '.', which is the end of the N-Quad
+            while label.ends_with('.') {
+                label.pop();
+                self.pos -= 1;
+            }
+            if label.is_empty() {
+                return Err(self.error_at(start, format!("Empty blank node {}", what)));
+            }
+            return Ok(format!("_:{}", label));
+        }
+
+        for function in &["uid", "val"] {
+            if !self.starts_with(&format!("{}(", function)) {
+                continue;
+            }
+            if *function == "val" && what == "subject" {
+                return Err(self.error("val() can only be used as an object"));
+            }
+
+            self.pos += function.len() + 1;
+            self.skip_whitespace();
+            let var = self.take_while(|c| c.is_alphanumeric() || c == '_' || c == '.');
+            self.skip_whitespace();
+            if var.is_empty() {
+                return Err(self.error(format!("Expected a variable name in {}()", function)));
+            }
+            self.expect(')')?;
+            return Ok(format!("{}({})", function, var));
+        }
+
+        match self.peek() {
+            Some('<') => self.iri(what),
+            Some(c) => Err(self.error(format!("Unexpected '{}', expected {}", c, what))),
+            None => Err(self.error(format!("Expected {}, found end of line", what))),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, DgraphError> {
+        let start = self.pos;
+        self.expect('"')?;
+
+        let mut s = String::new();
+        loop {
+            let c = self.peek().ok_or_else(|| self.error_at(start, "Unterminated string"))?;
+            self.pos += 1;
+            match c {
+                '"' => return Ok(s),
+                '\\' => s.push(self.escape()?),
+                c => s.push(c),
+            }
+        }
+    }
+
+    fn escape(&mut self) -> Result<char, DgraphError> {
+        let start = self.pos - 1;
+        let c = self.peek().ok_or_else(|| self.error_at(start, "Unterminated escape sequence"))?;
+        self.pos += 1;
+
+        let len = match c {
+            't' => return Ok('\t'),
+            'b' => return Ok('\u{8}'),
+            'n' => return Ok('\n'),
+            'r' => return Ok('\r'),
+            'f' => return Ok('\u{c}'),
+            '"' | '\'' | '\\' => return Ok(c),
+            'u' => 4,
+            'U' => 8,
+            c => return Err(self.error_at(start, format!("Invalid escape sequence '\\{}'", c))),
+        };
+
+        let hex: String = self.chars.iter().skip(self.pos).take(len).collect();
+        self.pos += hex.chars().count();
+        u32::from_str_radix(&hex, 16)
+            .ok()
+            .filter(|_| hex.len() == len)
+            .and_then(std::char::from_u32)
+            .ok_or_else(|| self.error_at(start, format!("Invalid unicode escape '\\{}{}'", c, hex)))
+    }
+
+    fn literal(&mut self, nquad: &mut api::NQuad) -> Result<(), DgraphError> {
+        let s = self.string()?;
+        let mut value = api::Value::new();
+
+        if self.peek() == Some('@') {
+            self.pos += 1;
+            nquad.lang = self.take_while(|c| c.is_ascii_alphanumeric() || c == '-');
+            if nquad.lang.is_empty() {
+                return Err(self.error("Expected a language tag"));
+            }
+            value.set_default_val(s);
+        } else if self.starts_with("^^") {
+            self.pos += 2;
+            let start = self.pos;
+            let xs_type = self.iri("type")?;
+            let invalid = || self.error_at(start, format!("Invalid {} value {:?}", xs_type, s));
+
+            match xs_type.as_str() {
+                "xs:string" => value.set_str_val(s),
+                "xs:int" | "xs:integer" => value.set_int_val(s.trim().parse().map_err(|_| invalid())?),
+                "xs:float" | "xs:double" => {
+                    let f: f64 = s.trim().parse().map_err(|_| invalid())?;
+                    if !f.is_finite() {
+                        return Err(invalid());
+                    }
+                    value.set_double_val(f)
+                }
+                "xs:boolean" => match s.trim() {
+                    "true" | "1" => value.set_bool_val(true),
+                    "false" | "0" => value.set_bool_val(false),
+                    _ => return Err(invalid()),
+                },
+                "xs:dateTime" | "xs:date" => {
+                    let (secs, nanos, offset) = datetime::parse_rfc3339(s.trim()).ok_or_else(invalid)?;
+                    value.set_datetime_val(datetime::encode_binary(secs, nanos, offset))
+                }
+                "xs:password" => value.set_password_val(s),
+                // The server parses GeoJSON when converting to the predicate's type
+                "geo:geojson" => value.set_default_val(s),
+                _ => return Err(self.error_at(start, format!("Unsupported type <{}>", xs_type))),
+            }
+        } else {
+            value.set_default_val(s);
+        }
+
+        nquad.set_object_value(value);
+        Ok(())
+    }
+
+    fn facets(&mut self) -> Result<Vec<api::Facet>, DgraphError> {
+        self.expect('(')?;
+        self.skip_whitespace();
+
+        let mut facets = Vec::new();
+        if self.peek() == Some(')') {
+            self.pos += 1;
+            return Ok(facets);
+        }
+
+        loop {
+            self.skip_whitespace();
+            let start = self.pos;
+            let key = self.take_while(|c| c.is_alphanumeric() || c == '_' || c == '-' || c == '.');
+            if key.is_empty() {
+                return Err(self.error("Expected a facet key"));
+            }
+            if facets.iter().any(|f: &api::Facet| f.key == key) {
+                return Err(self.error_at(start, format!("Duplicate facet {}", key)));
+            }
+
+            self.skip_whitespace();
+            self.expect('=')?;
+            self.skip_whitespace();
+
+            facets.push(self.facet(key)?);
+
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some(')') => {
+                    self.pos += 1;
+                    return Ok(facets);
+                }
+                Some(c) => return Err(self.error(format!("Expected ',' or ')', found '{}'", c))),
+                None => return Err(self.error("Unterminated facets")),
+            }
+        }
+    }
+
+    fn facet(&mut self, key: String) -> Result<api::Facet, DgraphError> {
+        if self.peek() == Some('"') {
+            return Ok(api::Facet::from((key, self.string()?)));
+        }
+
+        let start = self.pos;
+        let raw = self.take_while(|c| c != ',' && c != ')' && !c.is_whitespace());
+
+        if raw == "true" || raw == "false" {
+            Ok(api::Facet::from((key, raw == "true")))
+        } else if let Ok(i) = raw.parse::<i64>() {
+            Ok(api::Facet::from((key, i)))
+        } else if let Some(f) = raw.parse::<f64>().ok().filter(|f| f.is_finite()) {
+            Ok(api::Facet::from((key, f)))
+        } else if let Some((secs, nanos, offset)) = datetime::parse_rfc3339(&raw) {
+            // Encoded directly to keep the offset from UTC, which `FacetValue` doesn't hold
+            let mut facet = api::Facet::new();
+            facet.key = key;
+            facet.val_type = api::Facet_ValType::DATETIME;
+            facet.value = datetime::encode_binary(secs, nanos, offset);
+            Ok(facet)
+        } else if raw.is_empty() {
+            Err(self.error("Expected a facet value"))
+        } else {
+            Err(self.error_at(start, format!("Invalid facet value {:?}, strings must be quoted", raw)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let rdf = r#"
+            # A comment
+            _:alice <name> "Alice \"A\" é"@en .
+            <0x2a> <age> "29"^^<xs:int> .
+            uid(v) <friend> _:alice (since=2006-01-02T15:04:05Z, close=true, weight=0.5, rank=2, note="a, b") .
+            <0x2a> * * .
+        "#;
+
+        let nquads = parse(rdf).unwrap();
+        assert_eq!(nquads.len(), 4);
+
+        assert_eq!(nquads[0].subject, "_:alice");
+        assert_eq!(nquads[0].predicate, "name");
+        assert_eq!(nquads[0].get_object_value().get_default_val(), "Alice \"A\" é");
+        assert_eq!(nquads[0].lang, "en");
+
+        assert_eq!(nquads[1].subject, "0x2a");
+        assert_eq!(nquads[1].get_object_value().get_int_val(), 29);
+
+        assert_eq!(nquads[2].subject, "uid(v)");
+        assert_eq!(nquads[2].object_id, "_:alice");
+        let facets = &nquads[2].facets;
+        assert_eq!(facets.len(), 5);
+        assert_eq!(facets[0].val_type, api::Facet_ValType::DATETIME);
+        assert_eq!(facets[1].value, vec![1]);
+        assert_eq!(facets[2].value, 0.5f64.to_bits().to_le_bytes().to_vec());
+        assert_eq!(facets[3].value, 2i64.to_le_bytes().to_vec());
+        assert_eq!(facets[4].value, b"a, b".to_vec());
+
+        assert_eq!(nquads[3].predicate, STAR_ALL);
+        assert_eq!(nquads[3].get_object_value().get_default_val(), STAR_ALL);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let rdf = concat!(
+            "_:alice <name> \"Alice\\n\"@en .\n",
+            "<0x2a> <age> \"29\"^^<xs:int> .\n",
+            "<0x2a> <born> \"2006-01-02T15:04:05-07:00\"^^<xs:dateTime> .\n",
+            "uid(v) <friend> val(f) (since=2006-01-02T15:04:05Z, close=true, weight=1.0) .\n",
+            "_:a <friend> _:b (since=2020-01-01T10:00:00+02:00) .\n",
+            "<0x2a> * * .\n",
+        );
+
+        assert_eq!(to_string(&parse(rdf).unwrap()).unwrap(), rdf);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error_at = |rdf: &str| match parse(rdf) {
+            Err(DgraphError::InvalidRdf { line, column, .. }) => (line, column),
+            res => panic!("Expected an InvalidRdf error, got {:?}", res),
+        };
+
+        assert_eq!(error_at("_:a <name> \"Alice\" .\n_:a <age> \"x\"^^<xs:int> ."), (2, 16));
+        assert_eq!(error_at("_:a <name> \"Alice ."), (1, 12));
+        assert_eq!(error_at("_:a <name \"Alice\" ."), (1, 5));
+        assert_eq!(error_at("_:a <name> \"Alice\""), (1, 19));
+        assert_eq!(error_at("_:a <name> \"Alice\" . x"), (1, 22));
+        assert_eq!(error_at("val(v) <name> \"Alice\" ."), (1, 1));
+        assert_eq!(error_at("_:a <friend> _:b (since=yesterday) ."), (1, 25));
+        assert_eq!(error_at("_:a <age> \"1\"^^<xs:complex> ."), (1, 16));
+    }
+
+    #[test]
+    fn test_serialize_errors() {
+        let mut nquad = api::NQuad::new();
+        nquad.subject = "_:a".to_string();
+        nquad.predicate = "name".to_string();
+        assert!(nquad_to_string(&nquad).is_err());
+
+        let mut value = api::Value::new();
+        value.set_bytes_val(vec![1, 2, 3]);
+        nquad.set_object_value(value);
+        assert!(nquad_to_string(&nquad).is_err());
+    }
+}
@@ -0,0 +1,58 @@
+use crate::errors::DgraphError;
+use crate::protos::api;
+
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+impl api::Response {
+    /// Deserializes the whole json response
+    pub fn decode<T: DeserializeOwned>(&self) -> Result<T, DgraphError> {
+        serde_json::from_slice(&self.json)
+            .map_err(|e| DgraphError::DeserializeError { block: None, source: e })
+    }
+
+    /// Deserializes the results of a single named query block, such as `q0`
+    pub fn decode_block<T: DeserializeOwned>(&self, block: &str) -> Result<Vec<T>, DgraphError> {
+        let mut json: serde_json::Map<String, Value> = self.decode()?;
+
+        let block_json = json.remove(block)
+            .ok_or_else(|| DgraphError::MissingBlock(block.to_string()))?;
+
+        serde_json::from_value(block_json)
+            .map_err(|e| DgraphError::DeserializeError { block: Some(block.to_string()), source: e })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    struct Node {
+        uid: String,
+    }
+
+    #[test]
+    fn test_decode_block() {
+        let res = api::Response {
+            json: br#"{"q0": [{"uid": "0x1"}, {"uid": "0x2"}], "q1": [{"name": "foo"}]}"#.to_vec(),
+            ..Default::default()
+        };
+
+        let nodes: Vec<Node> = res.decode_block("q0").unwrap();
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(nodes[1].uid, "0x2");
+
+        match res.decode_block::<Node>("q1") {
+            Err(DgraphError::DeserializeError { block: Some(block), .. }) => assert_eq!(block, "q1"),
+            _ => panic!("Expected a DeserializeError for q1"),
+        }
+
+        match res.decode_block::<Node>("q2") {
+            Err(DgraphError::MissingBlock(block)) => assert_eq!(block, "q2"),
+            _ => panic!("Expected a MissingBlock error for q2"),
+        }
+    }
+}
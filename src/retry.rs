@@ -0,0 +1,61 @@
+use rand::Rng;
+use std::time::Duration;
+
+/// Controls how `DgraphClient::run_txn` retries aborted transactions
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    /// Exponential backoff with full jitter: a random delay between zero and
+    /// `base_delay * 2^attempt`, capped at `max_delay`
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let ceiling = self.base_delay
+            .checked_mul(2u32.saturating_pow(attempt))
+            .unwrap_or(self.max_delay)
+            .min(self.max_delay);
+
+        let ceiling_ms = ceiling.as_millis() as u64;
+        if ceiling_ms == 0 {
+            return ceiling;
+        }
+
+        Duration::from_millis(rand::thread_rng().gen_range(0, ceiling_ms + 1))
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            base_delay: Duration::from_millis(10),
+            max_delay: Duration::from_secs(1),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_is_capped() {
+        let policy = RetryPolicy {
+            max_attempts: 10,
+            base_delay: Duration::from_millis(10),
+            max_delay: Duration::from_millis(100),
+        };
+
+        for attempt in 0..64 {
+            assert!(policy.backoff(attempt) <= Duration::from_millis(100));
+        }
+
+        for _ in 0..100 {
+            assert!(policy.backoff(1) <= Duration::from_millis(20));
+        }
+    }
+}
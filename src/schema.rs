@@ -0,0 +1,816 @@
+//! A typed model of Dgraph schemas, which parses and renders schema text as in
+//!
+//! ```text
+//! node_key: string @index(hash) @upsert .
+//! children: [uid] @reverse @count .
+//! type Process {
+//!   node_key
+//!   children
+//! }
+//! ```
+//!
+//! `Schema::validate` catches mistakes the server would reject, such as a tokenizer that
+//! doesn't apply to the predicate's type, before the schema is sent with `alter_schema`.
+//!
+//! `Schema` also deserializes from the result of a `schema {}` query, which is how
+//! `DgraphClient::schema` reads the live schema.
+use crate::errors::DgraphError;
+
+use serde::Deserialize;
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScalarType {
+    Default,
+    Int,
+    Float,
+    String,
+    Bool,
+    DateTime,
+    Geo,
+    Password,
+    Uid,
+}
+
+impl ScalarType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ScalarType::Default => "default",
+            ScalarType::Int => "int",
+            ScalarType::Float => "float",
+            ScalarType::String => "string",
+            ScalarType::Bool => "bool",
+            ScalarType::DateTime => "datetime",
+            ScalarType::Geo => "geo",
+            ScalarType::Password => "password",
+            ScalarType::Uid => "uid",
+        }
+    }
+}
+
+impl fmt::Display for ScalarType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ScalarType {
+    type Err = DgraphError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let scalar_type = match s {
+            "default" => ScalarType::Default,
+            "int" => ScalarType::Int,
+            "float" => ScalarType::Float,
+            "string" => ScalarType::String,
+            "bool" => ScalarType::Bool,
+            "datetime" => ScalarType::DateTime,
+            "geo" => ScalarType::Geo,
+            "password" => ScalarType::Password,
+            "uid" => ScalarType::Uid,
+            _ => return Err(DgraphError::InvalidSchema(format!("Unknown type {:?}", s))),
+        };
+        Ok(scalar_type)
+    }
+}
+
+/// The tokenizers Dgraph knows, and the type each one indexes
+const TOKENIZERS: &[(&str, ScalarType)] = &[
+    ("exact", ScalarType::String),
+    ("hash", ScalarType::String),
+    ("term", ScalarType::String),
+    ("fulltext", ScalarType::String),
+    ("trigram", ScalarType::String),
+    ("int", ScalarType::Int),
+    ("float", ScalarType::Float),
+    ("bool", ScalarType::Bool),
+    ("geo", ScalarType::Geo),
+    ("year", ScalarType::DateTime),
+    ("month", ScalarType::DateTime),
+    ("day", ScalarType::DateTime),
+    ("hour", ScalarType::DateTime),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Predicate {
+    pub name: String,
+    pub scalar_type: ScalarType,
+    /// Whether the predicate holds a list, as in `[string]`
+    pub list: bool,
+    /// The tokenizers of `@index`
+    pub index: Vec<String>,
+    pub upsert: bool,
+    pub reverse: bool,
+    pub count: bool,
+    pub lang: bool,
+    pub noconflict: bool,
+}
+
+impl Predicate {
+    pub fn new(name: impl Into<String>, scalar_type: ScalarType) -> Self {
+        Self {
+            name: name.into(),
+            scalar_type,
+            list: false,
+            index: Vec::new(),
+            upsert: false,
+            reverse: false,
+            count: false,
+            lang: false,
+            noconflict: false,
+        }
+    }
+
+    pub fn list(mut self) -> Self {
+        self.list = true;
+        self
+    }
+
+    pub fn index<'a>(mut self, tokenizers: impl IntoIterator<Item=&'a str>) -> Self {
+        self.index.extend(tokenizers.into_iter().map(str::to_string));
+        self
+    }
+
+    pub fn upsert(mut self) -> Self {
+        self.upsert = true;
+        self
+    }
+
+    pub fn reverse(mut self) -> Self {
+        self.reverse = true;
+        self
+    }
+
+    pub fn count(mut self) -> Self {
+        self.count = true;
+        self
+    }
+
+    pub fn lang(mut self) -> Self {
+        self.lang = true;
+        self
+    }
+
+    pub fn has_index(&self, tokenizer: &str) -> bool {
+        self.index.iter().any(|t| t == tokenizer)
+    }
+
+    pub fn validate(&self) -> Result<(), DgraphError> {
+        let invalid = |message: String| Err(DgraphError::InvalidSchema(format!("{}: {}", self.name, message)));
+
+        if !is_name(&self.name) {
+            return invalid("Invalid predicate name".to_string());
+        }
+
+        for (i, tokenizer) in self.index.iter().enumerate() {
+            if self.index[..i].contains(tokenizer) {
+                return invalid(format!("Tokenizer {} is repeated", tokenizer));
+            }
+            match TOKENIZERS.iter().find(|(name, _)| name == tokenizer) {
+                None => return invalid(format!("Unknown tokenizer {}", tokenizer)),
+                Some((_, scalar_type)) if *scalar_type != self.scalar_type => return invalid(format!(
+                    "Tokenizer {} indexes {} values, not {}", tokenizer, scalar_type, self.scalar_type,
+                )),
+                Some(_) => (),
+            }
+        }
+
+        if self.upsert && self.index.is_empty() {
+            return invalid("@upsert requires an @index".to_string());
+        }
+        if self.reverse && self.scalar_type != ScalarType::Uid {
+            return invalid("@reverse requires the uid type".to_string());
+        }
+        if self.lang && self.scalar_type != ScalarType::String {
+            return invalid("@lang requires the string type".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Predicate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", name(&self.name))?;
+        if self.list {
+            write!(f, "[{}]", self.scalar_type)?;
+        } else {
+            write!(f, "{}", self.scalar_type)?;
+        }
+
+        if !self.index.is_empty() {
+            write!(f, " @index({})", self.index.join(", "))?;
+        }
+        for (set, directive) in &[
+            (self.upsert, "@upsert"),
+            (self.reverse, "@reverse"),
+            (self.count, "@count"),
+            (self.lang, "@lang"),
+            (self.noconflict, "@noconflict"),
+        ] {
+            if *set {
+                write!(f, " {}", directive)?;
+            }
+        }
+        write!(f, " .")
+    }
+}
+
+/// A `type` definition, listing the predicates of nodes with that `dgraph.type`
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeDef {
+    pub name: String,
+    pub fields: Vec<String>,
+}
+
+impl TypeDef {
+    pub fn new<I: Into<String>>(name: impl Into<String>, fields: impl IntoIterator<Item=I>) -> Self {
+        Self {
+            name: name.into(),
+            fields: fields.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), DgraphError> {
+        let invalid = |message: String| Err(DgraphError::InvalidSchema(format!("type {}: {}", self.name, message)));
+
+        if !is_name(&self.name) {
+            return invalid("Invalid type name".to_string());
+        }
+        for (i, field) in self.fields.iter().enumerate() {
+            if !is_name(field) {
+                return invalid(format!("Invalid field name {:?}", field));
+            }
+            if self.fields[..i].contains(field) {
+                return invalid(format!("Field {} is repeated", field));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for TypeDef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "type {} {{", name(&self.name))?;
+        for field in &self.fields {
+            writeln!(f, "  {}", name(field))?;
+        }
+        write!(f, "}}")
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(try_from = "SchemaResponse")]
+pub struct Schema {
+    pub predicates: Vec<Predicate>,
+    pub types: Vec<TypeDef>,
+}
+
+impl Schema {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses schema text. `#` comments are skipped, and type fields may use the older
+    /// `name: type` form, whose type is ignored.
+    pub fn parse(schema: &str) -> Result<Self, DgraphError> {
+        Parser::new(schema)?.schema()
+    }
+
+    pub fn predicate(&self, name: &str) -> Option<&Predicate> {
+        self.predicates.iter().find(|p| p.name == name)
+    }
+
+    pub fn type_def(&self, name: &str) -> Option<&TypeDef> {
+        self.types.iter().find(|t| t.name == name)
+    }
+
+    /// Checks that this schema has every predicate and type of `required`, with at least its
+    /// tokenizers, directives and fields, as in
+    ///
+    /// ```ignore
+    /// dg.schema().await?.check_includes(&"node_key: string @upsert @index(hash) .".parse()?)?;
+    /// ```
+    pub fn check_includes(&self, required: &Schema) -> Result<(), DgraphError> {
+        let mismatch = |message: String| Err(DgraphError::SchemaMismatch(message));
+
+        for r in &required.predicates {
+            let p = match self.predicate(&r.name) {
+                Some(p) => p,
+                None => return mismatch(format!("{} is missing", r.name)),
+            };
+            if p.scalar_type != r.scalar_type || p.list != r.list {
+                return mismatch(format!("{} is {}, not {}", r.name, type_string(p), type_string(r)));
+            }
+            if let Some(tokenizer) = r.index.iter().find(|t| !p.has_index(t)) {
+                return mismatch(format!("{} has no {} index", r.name, tokenizer));
+            }
+            for (has, required, directive) in &[
+                (p.upsert, r.upsert, "@upsert"),
+                (p.reverse, r.reverse, "@reverse"),
+                (p.count, r.count, "@count"),
+                (p.lang, r.lang, "@lang"),
+                (p.noconflict, r.noconflict, "@noconflict"),
+            ] {
+                if *required && !*has {
+                    return mismatch(format!("{} is missing {}", r.name, directive));
+                }
+            }
+        }
+
+        for r in &required.types {
+            let t = match self.type_def(&r.name) {
+                Some(t) => t,
+                None => return mismatch(format!("type {} is missing", r.name)),
+            };
+            if let Some(field) = r.fields.iter().find(|f| !t.fields.contains(f)) {
+                return mismatch(format!("type {} has no field {}", r.name, field));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks every predicate and type, and that none of them is defined twice
+    pub fn validate(&self) -> Result<(), DgraphError> {
+        for (i, predicate) in self.predicates.iter().enumerate() {
+            predicate.validate()?;
+            if self.predicates[..i].iter().any(|p| p.name == predicate.name) {
+                return Err(DgraphError::InvalidSchema(format!("{} is defined twice", predicate.name)));
+            }
+        }
+        for (i, type_def) in self.types.iter().enumerate() {
+            type_def.validate()?;
+            if self.types[..i].iter().any(|t| t.name == type_def.name) {
+                return Err(DgraphError::InvalidSchema(format!("type {} is defined twice", type_def.name)));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Schema {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for predicate in &self.predicates {
+            writeln!(f, "{}", predicate)?;
+        }
+        for type_def in &self.types {
+            writeln!(f, "{}", type_def)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Schema {
+    type Err = DgraphError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Schema::parse(s)
+    }
+}
+
+fn type_string(predicate: &Predicate) -> String {
+    if predicate.list {
+        format!("[{}]", predicate.scalar_type)
+    } else {
+        predicate.scalar_type.to_string()
+    }
+}
+
+/// The result of a `schema {}` query
+#[derive(Deserialize)]
+struct SchemaResponse {
+    #[serde(default)]
+    schema: Vec<SchemaNode>,
+    #[serde(default)]
+    types: Vec<TypeNode>,
+}
+
+#[derive(Deserialize)]
+struct SchemaNode {
+    predicate: String,
+    #[serde(rename = "type")]
+    scalar_type: String,
+    #[serde(default)]
+    list: bool,
+    #[serde(default)]
+    tokenizer: Vec<String>,
+    #[serde(default)]
+    upsert: bool,
+    #[serde(default)]
+    reverse: bool,
+    #[serde(default)]
+    count: bool,
+    #[serde(default)]
+    lang: bool,
+    #[serde(default)]
+    no_conflict: bool,
+}
+
+#[derive(Deserialize)]
+struct TypeNode {
+    name: String,
+    #[serde(default)]
+    fields: Vec<FieldNode>,
+}
+
+#[derive(Deserialize)]
+struct FieldNode {
+    name: String,
+}
+
+impl TryFrom<SchemaResponse> for Schema {
+    type Error = DgraphError;
+
+    fn try_from(res: SchemaResponse) -> Result<Self, Self::Error> {
+        let mut schema = Schema::new();
+        for node in res.schema {
+            schema.predicates.push(Predicate {
+                scalar_type: node.scalar_type.parse()?,
+                name: node.predicate,
+                list: node.list,
+                index: node.tokenizer,
+                upsert: node.upsert,
+                reverse: node.reverse,
+                count: node.count,
+                lang: node.lang,
+                noconflict: node.no_conflict,
+            });
+        }
+        for node in res.types {
+            schema.types.push(TypeDef::new(node.name, node.fields.into_iter().map(|f| f.name)));
+        }
+        Ok(schema)
+    }
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '.' || c == '-' || c == '~'
+}
+
+fn is_name(name: &str) -> bool {
+    !name.is_empty() && !name.chars().any(|c| "<>\"{}|\\^`".contains(c) || c.is_whitespace() || c.is_control())
+}
+
+/// Wraps names which aren't plain identifiers in `<>`
+fn name(name: &str) -> String {
+    if !name.starts_with('.') && !name.ends_with('.') && name.chars().all(is_name_char) {
+        name.to_string()
+    } else {
+        format!("<{}>", name)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Tok {
+    Name(String),
+    Punct(char),
+}
+
+struct Token {
+    tok: Tok,
+    line: usize,
+    column: usize,
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    /// Where the text ends, for errors at the end of input
+    end: (usize, usize),
+}
+
+impl Parser {
+    fn new(schema: &str) -> Result<Self, DgraphError> {
+        let error = |line, column, message: String| DgraphError::InvalidSchemaText { line, column, message };
+
+        let mut tokens = Vec::new();
+        let (mut line, mut column) = (1, 1);
+        let mut chars = schema.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            let start = (line, column);
+            let push = |tokens: &mut Vec<Token>, tok| tokens.push(Token { tok, line: start.0, column: start.1 });
+            column += 1;
+
+            match c {
+                '\n' => {
+                    line += 1;
+                    column = 1;
+                }
+                c if c.is_whitespace() => (),
+                '#' => {
+                    while chars.peek().is_some_and(|c| *c != '\n') {
+                        chars.next();
+                    }
+                }
+                '<' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('>') => break,
+                            Some('\n') | None => return Err(error(start.0, start.1, "Unterminated <name>".to_string())),
+                            Some(c) => name.push(c),
+                        }
+                        column += 1;
+                    }
+                    column += 1;
+                    push(&mut tokens, Tok::Name(name));
+                }
+                c if is_name_char(c) && c != '.' => {
+                    let mut name = c.to_string();
+                    while let Some(&next) = chars.peek() {
+                        // A '.' ends the statement unless the name continues after it
+                        if next == '.' {
+                            let mut after = chars.clone();
+                            after.next();
+                            if !after.peek().is_some_and(|c| is_name_char(*c) && *c != '.') {
+                                break;
+                            }
+                        } else if !is_name_char(next) {
+                            break;
+                        }
+                        name.push(next);
+                        chars.next();
+                        column += 1;
+                    }
+                    push(&mut tokens, Tok::Name(name));
+                }
+                ':' | '[' | ']' | '@' | '(' | ')' | ',' | '.' | '{' | '}' | '!' => push(&mut tokens, Tok::Punct(c)),
+                c => return Err(error(start.0, start.1, format!("Unexpected '{}'", c))),
+            }
+        }
+
+        Ok(Self { tokens, pos: 0, end: (line, column) })
+    }
+
+    fn error(&self, message: impl Into<String>) -> DgraphError {
+        let (line, column) = match self.tokens.get(self.pos) {
+            Some(token) => (token.line, token.column),
+            None => self.end,
+        };
+        DgraphError::InvalidSchemaText { line, column, message: message.into() }
+    }
+
+    fn peek(&self) -> Option<&Tok> {
+        self.tokens.get(self.pos).map(|t| &t.tok)
+    }
+
+    fn peek_punct(&self, punct: char) -> bool {
+        self.peek() == Some(&Tok::Punct(punct))
+    }
+
+    fn found(&self) -> String {
+        match self.peek() {
+            Some(Tok::Name(name)) => format!("{:?}", name),
+            Some(Tok::Punct(c)) => format!("'{}'", c),
+            None => "end of schema".to_string(),
+        }
+    }
+
+    fn expect(&mut self, punct: char) -> Result<(), DgraphError> {
+        if self.peek_punct(punct) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error(format!("Expected '{}', found {}", punct, self.found())))
+        }
+    }
+
+    fn name(&mut self, what: &str) -> Result<String, DgraphError> {
+        match self.peek() {
+            Some(Tok::Name(name)) => {
+                let name = name.clone();
+                self.pos += 1;
+                Ok(name)
+            }
+            _ => Err(self.error(format!("Expected {}, found {}", what, self.found()))),
+        }
+    }
+
+    fn schema(&mut self) -> Result<Schema, DgraphError> {
+        let mut schema = Schema::new();
+        while self.peek().is_some() {
+            let is_type = self.peek() == Some(&Tok::Name("type".to_string()))
+                && matches!(self.tokens.get(self.pos + 1).map(|t| &t.tok), Some(Tok::Name(_)));
+
+            if is_type {
+                schema.types.push(self.type_def()?);
+            } else {
+                schema.predicates.push(self.predicate()?);
+            }
+        }
+        Ok(schema)
+    }
+
+    /// A type, as in `[string]`, returning whether it is a list
+    fn scalar_type(&mut self) -> Result<(ScalarType, bool), DgraphError> {
+        let list = self.peek_punct('[');
+        if list {
+            self.pos += 1;
+        }
+
+        let start = self.pos;
+        let scalar_type = self.name("a type")?;
+        let scalar_type = scalar_type.parse().map_err(|_| {
+            self.pos = start;
+            self.error(format!("Unknown type {:?}", scalar_type))
+        })?;
+
+        if list {
+            self.expect(']')?;
+        }
+        Ok((scalar_type, list))
+    }
+
+    fn predicate(&mut self) -> Result<Predicate, DgraphError> {
+        let name = self.name("a predicate")?;
+        self.expect(':')?;
+        let (scalar_type, list) = self.scalar_type()?;
+
+        let mut predicate = Predicate::new(name, scalar_type);
+        predicate.list = list;
+
+        while self.peek_punct('@') {
+            self.pos += 1;
+            let start = self.pos;
+            let flag = match self.name("a directive")?.as_str() {
+                "index" => {
+                    self.expect('(')?;
+                    loop {
+                        predicate.index.push(self.name("a tokenizer")?);
+                        if self.peek_punct(',') {
+                            self.pos += 1;
+                        } else {
+                            break;
+                        }
+                    }
+                    self.expect(')')?;
+                    continue;
+                }
+                "upsert" => &mut predicate.upsert,
+                "reverse" => &mut predicate.reverse,
+                "count" => &mut predicate.count,
+                "lang" => &mut predicate.lang,
+                "noconflict" => &mut predicate.noconflict,
+                directive => {
+                    let message = format!("Unknown directive @{}", directive);
+                    self.pos = start;
+                    return Err(self.error(message));
+                }
+            };
+            *flag = true;
+        }
+
+        self.expect('.')?;
+        Ok(predicate)
+    }
+
+    fn type_def(&mut self) -> Result<TypeDef, DgraphError> {
+        self.pos += 1;
+        let name = self.name("a type name")?;
+        self.expect('{')?;
+
+        let mut fields = Vec::new();
+        while !self.peek_punct('}') {
+            fields.push(self.name("a field or '}'")?);
+            if self.peek_punct(':') {
+                self.pos += 1;
+                self.scalar_type()?;
+                if self.peek_punct('!') {
+                    self.pos += 1;
+                }
+            }
+        }
+        self.expect('}')?;
+
+        Ok(TypeDef { name, fields })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCHEMA: &str = "node_key: string @index(hash) @upsert .\n\
+                          process_name: string @index(exact, trigram) @lang .\n\
+                          created: datetime @index(hour) .\n\
+                          children: [uid] @reverse @count .\n\
+                          dgraph.graphql.schema: string .\n\
+                          <http://schema.org/name>: string .\n\
+                          type Process {\n  node_key\n  process_name\n  children\n}\n";
+
+    #[test]
+    fn test_parse_render() {
+        let schema = Schema::parse(SCHEMA).unwrap();
+        assert_eq!(schema.predicates.len(), 6);
+        assert_eq!(
+            schema.predicate("node_key").unwrap(),
+            &Predicate::new("node_key", ScalarType::String).index(["hash"]).upsert(),
+        );
+        assert_eq!(
+            schema.predicate("children").unwrap(),
+            &Predicate::new("children", ScalarType::Uid).list().reverse().count(),
+        );
+        assert!(schema.predicate("http://schema.org/name").is_some());
+        assert_eq!(
+            schema.type_def("Process").unwrap(),
+            &TypeDef::new("Process", ["node_key", "process_name", "children"]),
+        );
+        schema.validate().unwrap();
+        assert_eq!(schema.to_string(), SCHEMA);
+
+        // Compact text, comments and the older typed fields
+        let schema: Schema = "# processes\nname:string@index(term).age:[int].\n\
+                              type Person { name: string age: [int]! }"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            schema.to_string(),
+            "name: string @index(term) .\nage: [int] .\ntype Person {\n  name\n  age\n}\n",
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |schema: &str| match Schema::parse(schema) {
+            Err(DgraphError::InvalidSchemaText { line, column, .. }) => (line, column),
+            res => panic!("Expected an InvalidSchemaText error, got {:?}", res),
+        };
+
+        assert_eq!(error("name: string"), (1, 13));
+        assert_eq!(error("name: text ."), (1, 7));
+        assert_eq!(error("a: int .\nname: string @unique ."), (2, 15));
+        assert_eq!(error("name: string @index(hash ."), (1, 26));
+        assert_eq!(error("type Person {\n  name\n"), (3, 1));
+        assert_eq!(error("<name: string ."), (1, 1));
+    }
+
+    #[test]
+    fn test_validate() {
+        let invalid = |schema: &str| match Schema::parse(schema).unwrap().validate() {
+            Err(DgraphError::InvalidSchema(_)) => (),
+            res => panic!("Expected an InvalidSchema error for {:?}, got {:?}", schema, res),
+        };
+
+        invalid("age: int @index(hash) .");
+        invalid("name: string @index(hash, hash) .");
+        invalid("name: string @index(soundex) .");
+        invalid("friend: uid @index(exact) .");
+        invalid("name: string @upsert .");
+        invalid("name: string @reverse .");
+        invalid("age: int @lang .");
+        invalid("name: string .\nname: string @index(hash) .");
+        invalid("type Person { name name }");
+        invalid("type Person { name }\ntype Person { age }");
+
+        Schema::parse("friend: [uid] @reverse @count .\nloc: geo @index(geo) .\nscore: float @index(float) .")
+            .unwrap()
+            .validate()
+            .unwrap();
+    }
+
+    #[test]
+    fn test_decode_response() {
+        let res = br#"{
+            "schema": [
+                {"predicate": "dgraph.type", "type": "string", "index": true, "tokenizer": ["exact"], "list": true},
+                {"predicate": "node_key", "type": "string", "index": true, "tokenizer": ["hash"], "upsert": true},
+                {"predicate": "children", "type": "uid", "list": true, "reverse": true, "count": true},
+                {"predicate": "name", "type": "string", "lang": true}
+            ],
+            "types": [
+                {"name": "Process", "fields": [{"name": "node_key"}, {"name": "children"}]}
+            ]
+        }"#;
+        let schema: Schema = serde_json::from_slice(res).unwrap();
+
+        assert_eq!(
+            schema.predicate("node_key").unwrap(),
+            &Predicate::new("node_key", ScalarType::String).index(["hash"]).upsert(),
+        );
+        assert_eq!(schema.type_def("Process").unwrap(), &TypeDef::new("Process", ["node_key", "children"]));
+
+        let required = Schema::parse("node_key: string @upsert @index(hash) .\nchildren: [uid] @reverse .\n\
+                                      type Process { node_key }")
+            .unwrap();
+        schema.check_includes(&required).unwrap();
+
+        let mismatch = |required: &str| match schema.check_includes(&Schema::parse(required).unwrap()) {
+            Err(DgraphError::SchemaMismatch(_)) => (),
+            res => panic!("Expected a SchemaMismatch error for {:?}, got {:?}", required, res),
+        };
+        mismatch("pid: int .");
+        mismatch("node_key: string @index(hash, trigram) .");
+        mismatch("node_key: [string] .");
+        mismatch("name: string @count .");
+        mismatch("type File { node_key }");
+        mismatch("type Process { name }");
+
+        assert!(serde_json::from_slice::<Schema>(br#"{"schema": [{"predicate": "a", "type": "text"}]}"#).is_err());
+    }
+}
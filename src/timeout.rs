@@ -0,0 +1,68 @@
+use crate::errors::DgraphError;
+
+use futures::future::{self, Either};
+use std::future::Future;
+use std::time::Duration;
+
+/// Sends `timeout` to the server as the request's gRPC deadline
+pub(crate) fn set_deadline(options: &mut grpc::RequestOptions, timeout: Duration) {
+    options.metadata.add(
+        grpc::MetadataKey::from("grpc-timeout"),
+        bytes::Bytes::from(grpc_timeout(timeout)),
+    );
+}
+
+// The grpc-timeout header allows at most 8 digits, followed by a unit
+fn grpc_timeout(timeout: Duration) -> String {
+    const MAX: u128 = 99_999_999;
+
+    let millis = timeout.as_millis();
+    if millis <= MAX {
+        format!("{}m", millis.max(1))
+    } else {
+        format!("{}S", timeout.as_secs().min(MAX as u64))
+    }
+}
+
+/// Fails with `DgraphError::Timeout` if `fut` has not completed within `timeout`
+pub(crate) async fn with_timeout<T>(
+    timeout: Option<Duration>,
+    fut: impl Future<Output=Result<T, DgraphError>>,
+) -> Result<T, DgraphError> {
+    let timeout = match timeout {
+        Some(timeout) => timeout,
+        None => return fut.await,
+    };
+
+    futures::pin_mut!(fut);
+    match future::select(fut, futures_timer::Delay::new(timeout)).await {
+        Either::Left((res, _)) => res,
+        Either::Right(_) => Err(DgraphError::Timeout(timeout)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grpc_timeout() {
+        assert_eq!(grpc_timeout(Duration::from_millis(1500)), "1500m");
+        assert_eq!(grpc_timeout(Duration::from_micros(10)), "1m");
+        assert_eq!(grpc_timeout(Duration::from_secs(200_000)), "200000S");
+    }
+
+    #[test]
+    fn test_with_timeout() {
+        async_std::task::block_on(async {
+            let res = with_timeout(Some(Duration::from_millis(10)), future::pending::<Result<(), _>>()).await;
+            match res {
+                Err(DgraphError::Timeout(timeout)) => assert_eq!(timeout, Duration::from_millis(10)),
+                _ => panic!("Expected a timeout"),
+            }
+
+            let res = with_timeout(Some(Duration::from_secs(10)), future::ready(Ok(1))).await;
+            assert_eq!(res.unwrap(), 1);
+        });
+    }
+}
@@ -0,0 +1,571 @@
+use crate::errors::DgraphError;
+use crate::protos::api;
+
+use std::collections::HashMap;
+
+// Prefix of the query blocks added to count each variable used in a condition
+const LEN_BLOCK_PREFIX: &str = "dgraph_rs_len_";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOp {
+    Eq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl CompareOp {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "eq" => Some(CompareOp::Eq),
+            "lt" => Some(CompareOp::Lt),
+            "le" => Some(CompareOp::Le),
+            "gt" => Some(CompareOp::Gt),
+            "ge" => Some(CompareOp::Ge),
+            _ => None,
+        }
+    }
+
+    fn eval(self, lhs: u64, rhs: u64) -> bool {
+        match self {
+            CompareOp::Eq => lhs == rhs,
+            CompareOp::Lt => lhs < rhs,
+            CompareOp::Le => lhs <= rhs,
+            CompareOp::Gt => lhs > rhs,
+            CompareOp::Ge => lhs >= rhs,
+        }
+    }
+}
+
+/// A parsed upsert condition, such as `@if(eq(len(p), 0) AND gt(len(f), 1))`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Condition {
+    Len { op: CompareOp, var: String, value: u64 },
+    And(Box<Condition>, Box<Condition>),
+    Or(Box<Condition>, Box<Condition>),
+    Not(Box<Condition>),
+}
+
+impl Condition {
+    pub fn parse(cond: &str) -> Result<Self, DgraphError> {
+        let invalid = |message: &str| DgraphError::InvalidUpsert(format!("{}: {}", message, cond));
+
+        let tokens = tokenize(cond).ok_or_else(|| invalid("Unexpected character in condition"))?;
+        let mut parser = Parser { tokens: &tokens, pos: 0 };
+
+        if parser.next() != Some(&Token::Ident("@if".to_string())) {
+            return Err(invalid("Condition must start with @if"));
+        }
+        parser.expect(&Token::Open).ok_or_else(|| invalid("Expected ( after @if"))?;
+        let condition = parser.expr().ok_or_else(|| invalid("Invalid condition"))?;
+        parser.expect(&Token::Close).ok_or_else(|| invalid("Unbalanced parentheses in condition"))?;
+
+        if parser.pos != tokens.len() {
+            return Err(invalid("Unexpected trailing input in condition"));
+        }
+
+        Ok(condition)
+    }
+
+    /// The query variables this condition depends on
+    pub fn vars(&self) -> Vec<&str> {
+        let mut vars = Vec::new();
+        self.collect_vars(&mut vars);
+        vars
+    }
+
+    fn collect_vars<'a>(&'a self, vars: &mut Vec<&'a str>) {
+        match self {
+            Condition::Len { var, .. } => {
+                if !vars.contains(&var.as_str()) {
+                    vars.push(var);
+                }
+            }
+            Condition::And(lhs, rhs) | Condition::Or(lhs, rhs) => {
+                lhs.collect_vars(vars);
+                rhs.collect_vars(vars);
+            }
+            Condition::Not(cond) => cond.collect_vars(vars),
+        }
+    }
+
+    /// Evaluates the condition given the number of uids in each variable
+    pub fn eval(&self, lens: &HashMap<String, u64>) -> bool {
+        match self {
+            Condition::Len { op, var, value } => op.eval(lens.get(var).copied().unwrap_or(0), *value),
+            Condition::And(lhs, rhs) => lhs.eval(lens) && rhs.eval(lens),
+            Condition::Or(lhs, rhs) => lhs.eval(lens) || rhs.eval(lens),
+            Condition::Not(cond) => !cond.eval(lens),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Ident(String),
+    Int(u64),
+    Open,
+    Close,
+    Comma,
+}
+
+fn tokenize(s: &str) -> Option<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = s.char_indices().peekable();
+
+    while let Some((start, c)) = chars.next() {
+        match c {
+            c if c.is_whitespace() => (),
+            '(' => tokens.push(Token::Open),
+            ')' => tokens.push(Token::Close),
+            ',' => tokens.push(Token::Comma),
+            c if c.is_ascii_digit() => {
+                let mut end = start + 1;
+                while let Some((i, c)) = chars.peek() {
+                    if !c.is_ascii_digit() {
+                        break;
+                    }
+                    end = i + 1;
+                    chars.next();
+                }
+                tokens.push(Token::Int(s[start..end].parse().ok()?));
+            }
+            c if c == '@' || c == '_' || c.is_alphabetic() => {
+                let mut end = start + c.len_utf8();
+                while let Some((i, c)) = chars.peek() {
+                    if !(c.is_alphanumeric() || *c == '_' || *c == '.') {
+                        break;
+                    }
+                    end = i + c.len_utf8();
+                    chars.next();
+                }
+                tokens.push(Token::Ident(s[start..end].to_string()));
+            }
+            _ => return None,
+        }
+    }
+
+    Some(tokens)
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&'a Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<&'a Token> {
+        let token = self.tokens.get(self.pos);
+        self.pos += 1;
+        token
+    }
+
+    fn expect(&mut self, token: &Token) -> Option<()> {
+        if self.next()? == token {
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    fn keyword(&mut self, keyword: &str) -> bool {
+        match self.peek() {
+            Some(Token::Ident(ident)) if ident.eq_ignore_ascii_case(keyword) => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn expr(&mut self) -> Option<Condition> {
+        let mut lhs = self.term()?;
+        while self.keyword("or") {
+            lhs = Condition::Or(Box::new(lhs), Box::new(self.term()?));
+        }
+        Some(lhs)
+    }
+
+    fn term(&mut self) -> Option<Condition> {
+        let mut lhs = self.factor()?;
+        while self.keyword("and") {
+            lhs = Condition::And(Box::new(lhs), Box::new(self.factor()?));
+        }
+        Some(lhs)
+    }
+
+    fn factor(&mut self) -> Option<Condition> {
+        if self.keyword("not") {
+            return Some(Condition::Not(Box::new(self.factor()?)));
+        }
+
+        match self.next()? {
+            Token::Open => {
+                let cond = self.expr()?;
+                self.expect(&Token::Close)?;
+                Some(cond)
+            }
+            Token::Ident(func) => {
+                let op = CompareOp::parse(func)?;
+                self.expect(&Token::Open)?;
+                if !self.keyword("len") {
+                    return None;
+                }
+                self.expect(&Token::Open)?;
+                let var = match self.next()? {
+                    Token::Ident(var) => var.clone(),
+                    _ => return None,
+                };
+                self.expect(&Token::Close)?;
+                self.expect(&Token::Comma)?;
+                let value = match self.next()? {
+                    Token::Int(value) => *value,
+                    _ => return None,
+                };
+                self.expect(&Token::Close)?;
+                Some(Condition::Len { op, var, value })
+            }
+            _ => None,
+        }
+    }
+}
+
+// Functions whose result, when assigned with `v as ...`, is a value variable
+const VALUE_FUNCS: &[&str] = &["val", "math", "count", "min", "max", "sum", "avg"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum VarKind {
+    Uid,
+    Value,
+}
+
+/// Finds how `var` is defined in a query with `var as ...`. Blocks and edges define uid
+/// variables, while scalar predicates and functions such as `math` define value variables.
+fn var_kind(query: &str, var: &str) -> Option<VarKind> {
+    let is_word_char = |c: char| c.is_alphanumeric() || c == '_' || c == '.';
+
+    let mut words = Vec::new();
+    let mut start = None;
+    for (i, c) in query.char_indices().chain(std::iter::once((query.len(), ' '))) {
+        match (start, is_word_char(c)) {
+            (None, true) => start = Some(i),
+            (Some(s), false) => {
+                words.push((&query[s..i], i));
+                start = None;
+            }
+            _ => (),
+        }
+    }
+
+    let mut kind = None;
+    for triple in words.windows(3) {
+        let ((name, _), (as_, _), (target, end)) = (triple[0], triple[1], triple[2]);
+        if name != var || as_ != "as" {
+            continue;
+        }
+
+        let rest = query[end..].trim_start();
+        let is_value = if rest.starts_with('(') {
+            VALUE_FUNCS.contains(&target)
+        } else {
+            !rest.starts_with(['{', '@'])
+        };
+        if is_value {
+            return Some(VarKind::Value);
+        }
+        kind = Some(VarKind::Uid);
+    }
+
+    kind
+}
+
+/// Parses `cond`, checking that the variables it uses are uid variables defined in `query`
+fn check_condition(query: &str, cond: &str) -> Result<Condition, DgraphError> {
+    let condition = Condition::parse(cond)?;
+
+    for var in condition.vars() {
+        match var_kind(query, var) {
+            Some(VarKind::Uid) => (),
+            Some(VarKind::Value) => return Err(DgraphError::InvalidUpsert(
+                format!("Condition counts value variable {}, but len() only counts uid variables: {}", var, cond)
+            )),
+            None => return Err(DgraphError::InvalidUpsert(
+                format!("Condition uses variable {} which is not defined in the query: {}", var, cond)
+            )),
+        }
+    }
+
+    Ok(condition)
+}
+
+/// The position of the `}` closing the outer block of a query, skipping braces in strings,
+/// regexes and comments
+fn outer_block_end(query: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut chars = query.char_indices();
+    // A '/' starts a regex only where an argument can, otherwise it is a division in math()
+    let mut prev = '{';
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' | '/' if c == '"' || prev == ',' || prev == '(' => {
+                let mut escaped = false;
+                for (_, next) in chars.by_ref() {
+                    match next {
+                        _ if escaped => escaped = false,
+                        '\\' => escaped = true,
+                        next if next == c => break,
+                        _ => (),
+                    }
+                }
+            }
+            '#' => {
+                for (_, next) in chars.by_ref() {
+                    if next == '\n' {
+                        break;
+                    }
+                }
+            }
+            '{' => depth += 1,
+            '}' if depth == 1 => return Some(i),
+            '}' => depth -= 1,
+            _ => (),
+        }
+        if !c.is_whitespace() {
+            prev = c;
+        }
+    }
+
+    None
+}
+
+/// A query along with mutations that are each applied only if their condition holds.
+///
+/// Conditions may only use `len()` of uid variables. Which mutations fired is worked out on
+/// the client, from counts of those variables that are added to the query, since the server
+/// doesn't report it.
+#[derive(Debug, Clone)]
+pub struct ConditionalUpsert {
+    query: String,
+    mutations: Vec<(Option<Condition>, api::Mutation)>,
+}
+
+impl ConditionalUpsert {
+    pub fn new(query: impl Into<String>) -> Self {
+        Self {
+            query: query.into(),
+            mutations: Vec::new(),
+        }
+    }
+
+    /// Adds a mutation that applies only if `cond`, such as `@if(eq(len(p), 0))`, holds
+    pub fn mutation_if(mut self, cond: &str, mut mu: api::Mutation) -> Result<Self, DgraphError> {
+        let condition = check_condition(&self.query, cond)?;
+
+        mu.cond = cond.to_string();
+        self.mutations.push((Some(condition), mu));
+        Ok(self)
+    }
+
+    /// Adds a mutation that always applies, unless it already has a `cond`. That condition
+    /// is parsed and checked like one passed to `mutation_if` when the request is built.
+    pub fn mutation(mut self, mu: api::Mutation) -> Self {
+        self.mutations.push((None, mu));
+        self
+    }
+
+    /// Builds the request, adding a block that counts each variable used in a condition
+    pub(crate) fn into_request(mut self) -> Result<(api::Request, Vec<Option<Condition>>), DgraphError> {
+        for (condition, mu) in self.mutations.iter_mut() {
+            if condition.is_none() && !mu.cond.is_empty() {
+                *condition = Some(check_condition(&self.query, &mu.cond)?);
+            }
+        }
+
+        let mut vars: Vec<&str> = Vec::new();
+        for condition in self.mutations.iter().filter_map(|(cond, _)| cond.as_ref()) {
+            for var in condition.vars() {
+                if !vars.contains(&var) {
+                    vars.push(var);
+                }
+            }
+        }
+
+        let end = outer_block_end(&self.query)
+            .ok_or_else(|| DgraphError::InvalidUpsert(format!("Query has no closing brace: {}", self.query)))?;
+
+        let mut query = self.query[..end].to_string();
+        for var in vars {
+            query.push_str(&format!("\n{}{}(func: uid({})) {{ count(uid) }}", LEN_BLOCK_PREFIX, var, var));
+        }
+        query.push_str(&self.query[end..]);
+
+        let (conditions, mutations): (Vec<_>, Vec<_>) = self.mutations.into_iter().unzip();
+
+        let req = api::Request {
+            query,
+            mutations: mutations.into(),
+            ..Default::default()
+        };
+
+        Ok((req, conditions))
+    }
+}
+
+/// The result of a `ConditionalUpsert`
+#[derive(Debug, Clone)]
+pub struct UpsertResponse {
+    /// Whether each mutation was applied, in the order they were added. This is evaluated
+    /// on the client from the query results, not reported by the server.
+    pub fired: Vec<bool>,
+    /// The server response, with only the query blocks of the original query
+    pub response: api::Response,
+}
+
+impl UpsertResponse {
+    pub(crate) fn new(mut response: api::Response, conditions: Vec<Option<Condition>>) -> Result<Self, DgraphError> {
+        let mut json: serde_json::Map<String, serde_json::Value> = if response.json.is_empty() {
+            serde_json::Map::new()
+        } else {
+            response.decode()?
+        };
+
+        let block_names: Vec<String> = json.keys()
+            .filter(|key| key.starts_with(LEN_BLOCK_PREFIX))
+            .cloned()
+            .collect();
+
+        let mut lens = HashMap::new();
+        for block in block_names {
+            let len = json.remove(&block)
+                .as_ref()
+                .and_then(|value| value.get(0))
+                .and_then(|value| value.get("count"))
+                .and_then(|count| count.as_u64())
+                .unwrap_or(0);
+            lens.insert(block[LEN_BLOCK_PREFIX.len()..].to_string(), len);
+        }
+
+        response.json = serde_json::to_vec(&json).map_err(DgraphError::SerializeError)?;
+
+        let fired = conditions.iter()
+            .map(|cond| cond.as_ref().map(|cond| cond.eval(&lens)).unwrap_or(true))
+            .collect();
+
+        Ok(Self { fired, response })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_condition() {
+        let cond = Condition::parse("@if(eq(len(p), 0) AND NOT (gt(len(f), 1) or le(len(p), 3)))").unwrap();
+        assert_eq!(cond.vars(), vec!["p", "f"]);
+
+        let mut lens = HashMap::new();
+        lens.insert("p".to_string(), 0);
+        lens.insert("f".to_string(), 1);
+        assert!(!cond.eval(&lens));
+
+        let cond = Condition::parse("@if(eq(len(p), 0))").unwrap();
+        assert!(cond.eval(&HashMap::new()));
+
+        assert!(Condition::parse("eq(len(p), 0)").is_err());
+        assert!(Condition::parse("@if(eq(len(p), 0)").is_err());
+        assert!(Condition::parse("@if(eq(p, 0))").is_err());
+        assert!(Condition::parse("@if(has(len(p), 0))").is_err());
+        assert!(Condition::parse("@if(eq(len(p), 0) AND)").is_err());
+    }
+
+    #[test]
+    fn test_conditional_upsert() {
+        let query = r#"
+        {
+            p as var(func: eq(node_key, "{453120d4-5c9f-43f6-b7af-28b376b3a993}"))
+        }
+        "#;
+
+        assert!(ConditionalUpsert::new(query).mutation_if("@if(eq(len(q), 0))", api::Mutation::new()).is_err());
+
+        let (req, conditions) = ConditionalUpsert::new(query)
+            .mutation_if("@if(eq(len(p), 0))", api::Mutation::new()).unwrap()
+            .mutation_if("@if(gt(len(p), 0))", api::Mutation::new()).unwrap()
+            .mutation(api::Mutation::new())
+            .into_request()
+            .unwrap();
+
+        assert_eq!(req.mutations.len(), 3);
+        assert_eq!(req.mutations[0].cond, "@if(eq(len(p), 0))");
+        assert!(req.query.contains("dgraph_rs_len_p(func: uid(p)) { count(uid) }"));
+
+        let response = api::Response {
+            json: br#"{"dgraph_rs_len_p": [{"count": 1}]}"#.to_vec(),
+            ..Default::default()
+        };
+
+        let res = UpsertResponse::new(response, conditions).unwrap();
+        assert_eq!(res.fired, vec![false, true, true]);
+        assert_eq!(res.response.json, b"{}".to_vec());
+    }
+
+    #[test]
+    fn test_preset_condition() {
+        let query = "{ p as var(func: eq(name, \"}\")) } # }";
+
+        let mut mu = api::Mutation::new();
+        mu.cond = "@if(eq(len(p), 0))".to_string();
+        let (req, conditions) = ConditionalUpsert::new(query).mutation(mu).into_request().unwrap();
+        assert_eq!(conditions, vec![Some(Condition::parse("@if(eq(len(p), 0))").unwrap())]);
+        assert_eq!(
+            req.query,
+            "{ p as var(func: eq(name, \"}\")) \ndgraph_rs_len_p(func: uid(p)) { count(uid) }} # }",
+        );
+
+        let mut mu = api::Mutation::new();
+        mu.cond = "@if(eq(len(q), 0))".to_string();
+        assert!(ConditionalUpsert::new(query).mutation(mu).into_request().is_err());
+
+        let mut mu = api::Mutation::new();
+        mu.cond = "@if(has(p))".to_string();
+        assert!(ConditionalUpsert::new(query).mutation(mu).into_request().is_err());
+    }
+
+    #[test]
+    fn test_value_variables() {
+        let query = r#"
+        {
+            q(func: eq(node_key, "a")) {
+                p as uid
+                f as friend @filter(has(name)) { n as name }
+                c as count(friend)
+                s as math(c * 2)
+            }
+            v as var(func: uid(p))
+        }
+        "#;
+
+        assert_eq!(var_kind(query, "v"), Some(VarKind::Uid));
+        assert_eq!(var_kind(query, "f"), Some(VarKind::Uid));
+        assert_eq!(var_kind(query, "p"), Some(VarKind::Value));
+        assert_eq!(var_kind(query, "n"), Some(VarKind::Value));
+        assert_eq!(var_kind(query, "c"), Some(VarKind::Value));
+        assert_eq!(var_kind(query, "s"), Some(VarKind::Value));
+        assert_eq!(var_kind(query, "x"), None);
+
+        assert!(check_condition(query, "@if(eq(len(v), 0) AND gt(len(f), 1))").is_ok());
+        for cond in &["@if(eq(len(c), 0))", "@if(eq(len(s), 0))", "@if(eq(len(n), 0))"] {
+            match check_condition(query, cond) {
+                Err(DgraphError::InvalidUpsert(message)) => assert!(message.contains("value variable")),
+                res => panic!("Expected an InvalidUpsert error, got {:?}", res),
+            }
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use crate::datetime;
+use crate::errors::DgraphError;
+use crate::protos::api;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::time::SystemTime;
+
+/// A node uid, sent as `uid_val`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Uid(pub u64);
+
+impl std::fmt::Display for Uid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:#x}", self.0)
+    }
+}
+
+/// Parses uids as Dgraph writes them, such as `0x2a`
+impl std::str::FromStr for Uid {
+    type Err = DgraphError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.strip_prefix("0x")
+            .and_then(|hex| u64::from_str_radix(hex, 16).ok())
+            .map(Uid)
+            .ok_or_else(|| DgraphError::InvalidUid(s.to_string()))
+    }
+}
+
+impl Serialize for Uid {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Uid {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// A value for a `password` predicate, sent as `password_val`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Password(pub String);
+
+/// A WKB encoded geometry, sent as `geo_val`. See the `geo` module to build one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Geo(pub Vec<u8>);
+
+macro_rules! value_from {
+    ($ty:ty, $setter:ident, $conv:expr) => {
+        impl From<$ty> for api::Value {
+            fn from(v: $ty) -> Self {
+                let mut value = api::Value::new();
+                value.$setter($conv(v));
+                value
+            }
+        }
+    };
+}
+
+value_from!(i64, set_int_val, |v| v);
+value_from!(i32, set_int_val, i64::from);
+value_from!(u32, set_int_val, i64::from);
+value_from!(bool, set_bool_val, |v| v);
+value_from!(f64, set_double_val, |v| v);
+value_from!(f32, set_double_val, f64::from);
+value_from!(String, set_str_val, |v| v);
+value_from!(&str, set_str_val, String::from);
+value_from!(Vec<u8>, set_bytes_val, |v| v);
+value_from!(&[u8], set_bytes_val, <[u8]>::to_vec);
+value_from!(Uid, set_uid_val, |v: Uid| v.0);
+value_from!(Password, set_password_val, |v: Password| v.0);
+value_from!(Geo, set_geo_val, |v: Geo| v.0);
+
+impl From<SystemTime> for api::Value {
+    fn from(v: SystemTime) -> Self {
+        let (secs, nanos) = datetime::from_system_time(v);
+        let mut value = api::Value::new();
+        value.set_datetime_val(datetime::encode_binary(secs, nanos, None));
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn test_value_from() {
+        assert_eq!(api::Value::from(5i32).get_int_val(), 5);
+        assert_eq!(api::Value::from("bar.exe").get_str_val(), "bar.exe");
+        assert_eq!(api::Value::from(Uid(0x2a)).get_uid_val(), 0x2a);
+        assert!(api::Value::from(Password("hunter2".into())).has_password_val());
+        assert_eq!(Uid(0x2a).to_string(), "0x2a");
+        assert_eq!("0x2a".parse::<Uid>().unwrap(), Uid(0x2a));
+        assert!("42".parse::<Uid>().is_err());
+        assert_eq!(serde_json::to_value(Uid(0x2a)).unwrap(), serde_json::json!("0x2a"));
+    }
+
+    #[test]
+    fn test_encode_datetime() {
+        // Matches time.Unix(1, 5).UTC().MarshalBinary() in Go
+        let value = api::Value::from(UNIX_EPOCH + Duration::new(1, 5));
+        assert_eq!(
+            value.get_datetime_val(),
+            &[1, 0, 0, 0, 14, 119, 145, 247, 1, 0, 0, 0, 5, 255, 255][..],
+        );
+    }
+}
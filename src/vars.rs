@@ -0,0 +1,393 @@
+//! Typed query variables.
+//!
+//! ```ignore
+//! let vars = Vars::new()
+//!     .string("key", node_key)
+//!     .int("limit", 10);
+//!
+//! // Sent as `query q($key: string, $limit: int) { ... }`
+//! let res = txn.query_vars(r#"{
+//!     q0(func: eq(node_key, $key), first: $limit) { uid }
+//! }"#, &vars).await?;
+//! ```
+use crate::errors::DgraphError;
+use crate::query::name_ref;
+use crate::value::Uid;
+
+use std::collections::HashMap;
+
+/// A variable value, along with the GraphQL+- type it is declared as
+#[derive(Debug, Clone, PartialEq)]
+pub enum VarValue {
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    String(String),
+    /// Declared as a `string`, for use in `uid($var)`
+    Uids(Vec<Uid>),
+}
+
+impl VarValue {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            VarValue::Int(_) => "int",
+            VarValue::Float(_) => "float",
+            VarValue::Bool(_) => "bool",
+            VarValue::String(_) | VarValue::Uids(_) => "string",
+        }
+    }
+
+    fn to_var_string(&self) -> String {
+        match self {
+            VarValue::Int(i) => i.to_string(),
+            VarValue::Float(f) => f.to_string(),
+            VarValue::Bool(b) => b.to_string(),
+            VarValue::String(s) => s.clone(),
+            VarValue::Uids(uids) => {
+                let uids: Vec<_> = uids.iter().map(Uid::to_string).collect();
+                format!("[{}]", uids.join(", "))
+            }
+        }
+    }
+}
+
+macro_rules! var_value_from {
+    ($ty:ty, $variant:ident, $conv:expr) => {
+        impl From<$ty> for VarValue {
+            fn from(v: $ty) -> Self {
+                VarValue::$variant($conv(v))
+            }
+        }
+    };
+}
+
+var_value_from!(i64, Int, |v| v);
+var_value_from!(i32, Int, i64::from);
+var_value_from!(u32, Int, i64::from);
+var_value_from!(f64, Float, |v| v);
+var_value_from!(f32, Float, f64::from);
+var_value_from!(bool, Bool, |v| v);
+var_value_from!(String, String, |v| v);
+var_value_from!(&str, String, str::to_string);
+var_value_from!(Uid, Uids, |v| vec![v]);
+var_value_from!(Vec<Uid>, Uids, |v| v);
+
+/// Variables for `Txn::query_vars`, kept in the order they were added
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Vars {
+    vars: Vec<(String, VarValue)>,
+}
+
+#[derive(Debug, PartialEq)]
+struct Declaration {
+    name: String,
+    type_name: String,
+    has_default: bool,
+}
+
+impl Vars {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets `name`, with or without its leading `$`, replacing any previous value
+    pub fn set(mut self, name: &str, value: impl Into<VarValue>) -> Self {
+        let name = name.trim_start_matches('$').to_string();
+        let value = value.into();
+        match self.vars.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, v)) => *v = value,
+            None => self.vars.push((name, value)),
+        }
+        self
+    }
+
+    pub fn int(self, name: &str, value: i64) -> Self {
+        self.set(name, value)
+    }
+
+    pub fn float(self, name: &str, value: f64) -> Self {
+        self.set(name, value)
+    }
+
+    pub fn bool(self, name: &str, value: bool) -> Self {
+        self.set(name, value)
+    }
+
+    pub fn string(self, name: &str, value: impl Into<String>) -> Self {
+        self.set(name, value.into())
+    }
+
+    pub fn uids(self, name: &str, uids: impl IntoIterator<Item=Uid>) -> Self {
+        self.set(name, uids.into_iter().collect::<Vec<_>>())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&VarValue> {
+        let name = name.trim_start_matches('$');
+        self.vars.iter().find(|(n, _)| n == name).map(|(_, v)| v)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vars.is_empty()
+    }
+
+    /// The declaration for these variables, as in `query name($a: int, $b: string)`
+    pub fn declaration(&self, name: &str) -> String {
+        let vars: Vec<_> = self.vars.iter()
+            .map(|(n, v)| format!("${}: {}", n, v.type_name()))
+            .collect();
+        format!("query {}({})", name, vars.join(", "))
+    }
+
+    /// The variables as `query_with_vars` takes them
+    pub fn to_map(&self) -> HashMap<String, String> {
+        self.vars.iter()
+            .map(|(n, v)| (format!("${}", n), v.to_var_string()))
+            .collect()
+    }
+
+    /// Prepares `query` to be sent with these variables.
+    ///
+    /// A query without a declaration gets one generated, and an existing declaration is
+    /// checked against the variables. Either way, every variable the query uses must be
+    /// declared, and every declared variable without a default must be supplied.
+    pub fn apply(&self, query: &str) -> Result<String, DgraphError> {
+        let invalid = |message: String| Err(DgraphError::InvalidVars(message));
+
+        // Names are written into the declaration as they are
+        for (n, _) in &self.vars {
+            if name_ref(n).is_err() {
+                return invalid(format!("Invalid variable name {:?}", n));
+            }
+        }
+
+        let Header { name, declared, body } = split_query(query)?;
+        let declared_names: Vec<&str> = match &declared {
+            Some(declared) => declared.iter().map(|d| d.name.as_str()).collect(),
+            None => self.vars.iter().map(|(n, _)| n.as_str()).collect(),
+        };
+
+        for used in used_vars(body) {
+            if !declared_names.contains(&used) {
+                return invalid(format!("${} is used but not declared", used));
+            }
+        }
+
+        let declared = match declared {
+            Some(declared) => declared,
+            None if self.vars.is_empty() => return Ok(query.to_string()),
+            None => return Ok(format!("{} {}", self.declaration(name.unwrap_or("q")), body)),
+        };
+
+        for (n, v) in &self.vars {
+            match declared.iter().find(|d| d.name == *n) {
+                None => return invalid(format!("${} is not declared", n)),
+                // Ints are valid floats, so the server accepts them
+                Some(d) if d.type_name == v.type_name()
+                    || (d.type_name == "float" && v.type_name() == "int") => (),
+                Some(d) => return invalid(format!(
+                    "${} is declared as {} but is a {}", n, d.type_name, v.type_name(),
+                )),
+            }
+        }
+        for d in &declared {
+            if !d.has_default && self.get(&d.name).is_none() {
+                return invalid(format!("${} is declared but not supplied", d.name));
+            }
+        }
+
+        Ok(query.to_string())
+    }
+}
+
+struct Header<'a> {
+    name: Option<&'a str>,
+    declared: Option<Vec<Declaration>>,
+    body: &'a str,
+}
+
+/// Splits a query into its name, declarations and body, which starts at the first `{`
+fn split_query(query: &str) -> Result<Header<'_>, DgraphError> {
+    let invalid = |message: &str| DgraphError::InvalidVars(message.to_string());
+
+    let trimmed = query.trim_start();
+    let header = match trimmed.strip_prefix("query") {
+        Some(header) if header.starts_with(|c: char| c.is_whitespace() || c == '(') => header,
+        _ => return Ok(Header { name: None, declared: None, body: trimmed }),
+    };
+
+    let body_start = header.find('{').ok_or_else(|| invalid("Query has no body"))?;
+    let (header, body) = header.split_at(body_start);
+
+    let (name, declarations) = match header.find('(') {
+        Some(i) => {
+            let end = header.rfind(')').ok_or_else(|| invalid("Unterminated variable declaration"))?;
+            (header[..i].trim(), Some(&header[i + 1..end]))
+        }
+        None => (header.trim(), None),
+    };
+    let name = if name.is_empty() { None } else { Some(name) };
+
+    let declared = match declarations {
+        Some(declarations) => Some(parse_declarations(declarations)?),
+        None => None,
+    };
+
+    Ok(Header { name, declared, body })
+}
+
+fn parse_declarations(declarations: &str) -> Result<Vec<Declaration>, DgraphError> {
+    split_outside_quotes(declarations, ',')
+        .into_iter()
+        .filter(|d| !d.trim().is_empty())
+        .map(|d| {
+            let invalid = || DgraphError::InvalidVars(format!("Invalid declaration {:?}", d.trim()));
+
+            let (d, has_default) = match split_outside_quotes(d, '=').as_slice() {
+                [d] => (*d, false),
+                [d, _] => (*d, true),
+                _ => return Err(invalid()),
+            };
+            let mut parts = d.splitn(2, ':');
+            let name = parts.next().unwrap().trim();
+            let type_name = parts.next().ok_or_else(invalid)?.trim().trim_end_matches('!');
+            let name = name.strip_prefix('$').ok_or_else(invalid)?;
+
+            Ok(Declaration { name: name.to_string(), type_name: type_name.to_string(), has_default })
+        })
+        .collect()
+}
+
+fn split_outside_quotes(s: &str, sep: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut in_quotes = false;
+    let mut escaped = false;
+
+    for (i, c) in s.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_quotes => escaped = true,
+            '"' => in_quotes = !in_quotes,
+            c if c == sep && !in_quotes => {
+                parts.push(&s[start..i]);
+                start = i + c.len_utf8();
+            }
+            _ => (),
+        }
+    }
+    parts.push(&s[start..]);
+    parts
+}
+
+/// The `$variables` used in a query body, skipping strings, regexes and comments
+fn used_vars(body: &str) -> Vec<&str> {
+    let mut used = Vec::new();
+    let mut chars = body.char_indices().peekable();
+    // A '/' starts a regex only where an argument can, otherwise it is a division in math()
+    let mut prev = '{';
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' | '/' if c == '"' || prev == ',' || prev == '(' => {
+                let mut escaped = false;
+                for (_, next) in chars.by_ref() {
+                    match next {
+                        _ if escaped => escaped = false,
+                        '\\' => escaped = true,
+                        next if next == c => break,
+                        _ => (),
+                    }
+                }
+            }
+            '#' => {
+                for (_, next) in chars.by_ref() {
+                    if next == '\n' {
+                        break;
+                    }
+                }
+            }
+            '$' => {
+                let start = i + 1;
+                let mut end = start;
+                while let Some((j, next)) = chars.peek() {
+                    if !(next.is_alphanumeric() || *next == '_') {
+                        break;
+                    }
+                    end = j + next.len_utf8();
+                    chars.next();
+                }
+                if end > start && !used.contains(&&body[start..end]) {
+                    used.push(&body[start..end]);
+                }
+            }
+            _ => (),
+        }
+        if !c.is_whitespace() {
+            prev = c;
+        }
+    }
+
+    used
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_declaration() {
+        let vars = Vars::new()
+            .string("$key", "a")
+            .int("limit", 10)
+            .float("score", 0.5)
+            .bool("active", true)
+            .uids("ids", vec![Uid(1), Uid(0x2a)]);
+
+        let query = vars.apply("{ q(func: uid($ids), first: $limit) @filter(eq(key, $key)) { uid } }").unwrap();
+        assert_eq!(
+            query,
+            "query q($key: string, $limit: int, $score: float, $active: bool, $ids: string) \
+             { q(func: uid($ids), first: $limit) @filter(eq(key, $key)) { uid } }",
+        );
+
+        let map = vars.to_map();
+        assert_eq!(map["$limit"], "10");
+        assert_eq!(map["$active"], "true");
+        assert_eq!(map["$ids"], "[0x1, 0x2a]");
+
+        let query = Vars::new().int("a", 1).apply("query named { q(func: has(a), first: $a) { uid } }").unwrap();
+        assert!(query.starts_with("query named($a: int) {"));
+    }
+
+    #[test]
+    fn test_validate() {
+        let query = r#"query q($key: string, $limit: int = 10, $score: float) {
+            q(func: eq(key, $key), first: $limit) @filter(regexp(name, /^a$/) AND ge(score, $score)) {
+                uid # $ignored
+                name: val("$ignored")
+            }
+        }"#;
+
+        let vars = Vars::new().string("key", "a").int("score", 1);
+        assert_eq!(vars.apply(query).unwrap(), query);
+
+        let invalid = |vars: Vars, query: &str| match vars.apply(query) {
+            Err(DgraphError::InvalidVars(_)) => (),
+            res => panic!("Expected an InvalidVars error, got {:?}", res),
+        };
+
+        // Not supplied
+        invalid(Vars::new().int("score", 1), query);
+        // Not declared
+        invalid(Vars::new().string("key", "a").int("score", 1).bool("extra", true), query);
+        // Wrong type
+        invalid(Vars::new().int("key", 1).int("score", 1), query);
+        // Used without a declaration
+        invalid(Vars::new().int("a", 1), "{ q(func: has(a), first: $b) { uid } }");
+        invalid(Vars::new(), "{ q(func: has(a), first: $b) { uid } }");
+        // Invalid names
+        invalid(Vars::new().int("a: int) { x(func: has(secret)) { uid } } query y(", 1), "{ q(func: has(a)) { uid } }");
+        invalid(Vars::new().int("a b", 1), "{ q(func: has(a)) { uid } }");
+        invalid(Vars::new().int("1a", 1), "{ q(func: has(a)) { uid } }");
+    }
+}
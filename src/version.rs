@@ -0,0 +1,193 @@
+use crate::errors::DgraphError;
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+/// The oldest server version this client supports
+pub const MIN_SUPPORTED_VERSION: ServerVersion = ServerVersion {
+    major: 1,
+    minor: 1,
+    patch: 0,
+    pre: None,
+    build: None,
+};
+
+/// A Dgraph server version, parsed from the tag returned by `CheckVersion`, such as `v1.1.0`
+/// or `v1.1.0-rc2`. Builds between releases report a `git describe` tag, such as
+/// `v1.1.0-12-gabc123`, whose `12-gabc123` suffix is kept as build metadata rather than taken
+/// for a pre-release.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ServerVersion {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+    pub pre: Option<String>,
+    /// Build metadata, which doesn't affect precedence
+    pub build: Option<String>,
+}
+
+impl ServerVersion {
+    pub fn is_supported(&self) -> bool {
+        self.precedence(&MIN_SUPPORTED_VERSION) != Ordering::Less
+    }
+
+    /// Orders versions as semver does, so a pre-release comes before its release
+    fn precedence(&self, other: &ServerVersion) -> Ordering {
+        let release = (self.major, self.minor, self.patch).cmp(&(other.major, other.minor, other.patch));
+        if release != Ordering::Equal {
+            return release;
+        }
+
+        match (&self.pre, &other.pre) {
+            (None, None) => Ordering::Equal,
+            (None, Some(_)) => Ordering::Greater,
+            (Some(_), None) => Ordering::Less,
+            (Some(pre), Some(other)) => {
+                let mut ids = pre.split('.');
+                let mut other_ids = other.split('.');
+                loop {
+                    let ordering = match (ids.next(), other_ids.next()) {
+                        (None, None) => return Ordering::Equal,
+                        (None, Some(_)) => return Ordering::Less,
+                        (Some(_), None) => return Ordering::Greater,
+                        // Numeric identifiers are compared numerically, and come before others
+                        (Some(a), Some(b)) => match (a.parse::<u64>(), b.parse::<u64>()) {
+                            (Ok(a), Ok(b)) => a.cmp(&b),
+                            (Ok(_), Err(_)) => Ordering::Less,
+                            (Err(_), Ok(_)) => Ordering::Greater,
+                            (Err(_), Err(_)) => a.cmp(b),
+                        },
+                    };
+                    if ordering != Ordering::Equal {
+                        return ordering;
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for ServerVersion {
+    type Err = DgraphError;
+
+    fn from_str(tag: &str) -> Result<Self, Self::Err> {
+        let invalid = || DgraphError::InvalidVersion(tag.to_string());
+
+        let version = tag.trim();
+        let version = version.trim_start_matches('v');
+
+        let (version, build) = match version.find('+') {
+            Some(i) => (&version[..i], Some(version[i + 1..].to_string())),
+            None => match describe_suffix(version) {
+                Some(i) => (&version[..i], Some(version[i + 1..].to_string())),
+                None => (version, None),
+            },
+        };
+
+        let (version, pre) = match version.find('-') {
+            Some(i) => (&version[..i], Some(version[i + 1..].to_string())),
+            None => (version, None),
+        };
+
+        let mut parts = version.split('.');
+        let mut next = || -> Result<u64, DgraphError> {
+            parts.next()
+                .ok_or_else(invalid)?
+                .parse()
+                .map_err(|_| invalid())
+        };
+
+        let major = next()?;
+        let minor = next()?;
+        let patch = next()?;
+
+        if parts.next().is_some() {
+            return Err(invalid());
+        }
+
+        Ok(Self { major, minor, patch, pre, build })
+    }
+}
+
+/// The position of the `-` starting a `-<commits>-g<sha>` suffix added by `git describe`
+fn describe_suffix(version: &str) -> Option<usize> {
+    let mut parts = version.rsplitn(3, '-');
+    let sha = parts.next()?.strip_prefix('g')?;
+    let commits = parts.next()?;
+    let rest = parts.next()?;
+
+    let is_sha = !sha.is_empty() && sha.chars().all(|c| c.is_ascii_hexdigit());
+    let is_count = !commits.is_empty() && commits.chars().all(|c| c.is_ascii_digit());
+    if is_sha && is_count {
+        Some(rest.len())
+    } else {
+        None
+    }
+}
+
+impl fmt::Display for ServerVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "v{}.{}.{}", self.major, self.minor, self.patch)?;
+        if let Some(pre) = &self.pre {
+            write!(f, "-{}", pre)?;
+        }
+        if let Some(build) = &self.build {
+            write!(f, "+{}", build)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_version() {
+        let version: ServerVersion = "v1.1.0".parse().unwrap();
+        assert_eq!(version, ServerVersion { major: 1, minor: 1, patch: 0, pre: None, build: None });
+        assert!(version.is_supported());
+
+        let version: ServerVersion = "v20.03.1-rc1".parse().unwrap();
+        assert_eq!(version.major, 20);
+        assert_eq!(version.minor, 3);
+        assert_eq!(version.pre, Some("rc1".to_string()));
+        assert_eq!(version.to_string(), "v20.3.1-rc1");
+        assert!(version.is_supported());
+
+        let version: ServerVersion = "v1.0.18".parse().unwrap();
+        assert!(!version.is_supported());
+
+        // Pre-releases of the minimum version come before it
+        let version: ServerVersion = "v1.1.0-rc1".parse().unwrap();
+        assert!(!version.is_supported());
+        assert!("v1.1.1-rc1".parse::<ServerVersion>().unwrap().is_supported());
+
+        let precedence = |a: &str, b: &str| a.parse::<ServerVersion>().unwrap()
+            .precedence(&b.parse().unwrap());
+        assert_eq!(precedence("v1.1.0-rc.2", "v1.1.0-rc.10"), Ordering::Less);
+        assert_eq!(precedence("v1.1.0-rc", "v1.1.0-rc.1"), Ordering::Less);
+        assert_eq!(precedence("v1.1.0-1", "v1.1.0-beta"), Ordering::Less);
+        assert_eq!(precedence("v1.1.0-beta", "v1.1.0-alpha"), Ordering::Greater);
+
+        // Builds after a release are that release, not a pre-release of it
+        let version: ServerVersion = "v1.1.0-12-gabc123".parse().unwrap();
+        assert_eq!(version.pre, None);
+        assert_eq!(version.build, Some("12-gabc123".to_string()));
+        assert_eq!(version.to_string(), "v1.1.0+12-gabc123");
+        assert!(version.is_supported());
+
+        let version: ServerVersion = "v1.1.0-rc1-3-g0f1e2d".parse().unwrap();
+        assert_eq!(version.pre, Some("rc1".to_string()));
+        assert_eq!(version.build, Some("3-g0f1e2d".to_string()));
+        assert!(!version.is_supported());
+
+        let version: ServerVersion = "v1.1.0+linux".parse().unwrap();
+        assert_eq!(version.build, Some("linux".to_string()));
+        assert_eq!(precedence("v1.1.0+a", "v1.1.0-7-gffffff"), Ordering::Equal);
+
+        assert!("v1.1".parse::<ServerVersion>().is_err());
+        assert!("latest".parse::<ServerVersion>().is_err());
+    }
+}